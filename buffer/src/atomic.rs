@@ -0,0 +1,89 @@
+use std::fs;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufWriter, Error, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+
+// Saves to `path` what `contents` writes, returning the bytes it wrote. The
+// contents go to a temporary file beside `path` that is renamed over it once
+// it is on disk, so a failed save leaves the file as it was. With `backup`
+// the previous contents are kept in `path~`. A symlink is saved through to
+// the file it points at.
+pub fn write_file<F>(path: &Path,
+                     backup: bool,
+                     contents: F)
+                     -> io::Result<usize>
+  where F: FnOnce(&mut Write) -> io::Result<usize>
+{
+  let path = fs::canonicalize(path).unwrap_or(path.to_path_buf());
+  let name = match path.file_name() {
+    Some(name) => name.to_string_lossy().into_owned(),
+    None => return Err(Error::new(ErrorKind::InvalidInput, "not a file")),
+  };
+  let temp = path.with_file_name(format!(".{}.{}~", name, process::id()));
+  let saved = write_temp(&temp, &path, contents).and_then(|written| {
+    if backup && path.exists() {
+      try!(fs::copy(&path, path.with_file_name(format!("{}~", name))));
+    }
+    try!(fs::rename(&temp, &path));
+    sync_dir(&path);
+    Ok(written)
+  });
+  if saved.is_err() {
+    let _ = fs::remove_file(&temp);
+  }
+  saved
+}
+
+// Writes the new file `temp` with the permissions and owner of `like` if it
+// exists.
+fn write_temp<F>(temp: &Path, like: &Path, contents: F) -> io::Result<usize>
+  where F: FnOnce(&mut Write) -> io::Result<usize>
+{
+  let file = try!(OpenOptions::new().write(true).create_new(true).open(temp));
+  if let Ok(meta) = fs::metadata(like) {
+    try!(fs::set_permissions(temp, meta.permissions()));
+    set_owner(temp, &meta);
+  }
+  let mut out = BufWriter::new(file);
+  let written = try!(contents(&mut out));
+  let file = try!(out.into_inner());
+  try!(file.sync_all());
+  Ok(written)
+}
+
+// Gives `path` the owner and group in `meta`. Only root may give files
+// away, so otherwise a saved file may end up owned by whoever saved it.
+#[cfg(unix)]
+fn set_owner(path: &Path, meta: &fs::Metadata) {
+  use libc;
+  use std::ffi::CString;
+  use std::os::unix::ffi::OsStrExt;
+  use std::os::unix::fs::MetadataExt;
+
+  if let Ok(path) = CString::new(path.as_os_str().as_bytes()) {
+    unsafe {
+      libc::chown(path.as_ptr(), meta.uid(), meta.gid());
+    }
+  }
+}
+
+#[cfg(not(unix))]
+fn set_owner(_: &Path, _: &fs::Metadata) {}
+
+// Flushes the rename of `path` to disk along with its directory.
+#[cfg(unix)]
+fn sync_dir(path: &Path) {
+  let dir = match path.parent() {
+    Some(dir) if !dir.as_os_str().is_empty() => dir,
+    _ => Path::new("."),
+  };
+  if let Ok(dir) = File::open(dir) {
+    let _ = dir.sync_all();
+  }
+}
+
+// Directories can't be opened as files on Windows.
+#[cfg(not(unix))]
+fn sync_dir(_: &Path) {}
@@ -0,0 +1,367 @@
+use std::cmp;
+use std::io;
+use std::path::{Path, PathBuf};
+use text::clip;
+use textbox::*;
+use {Buffer, Editable, LineEnding, Navigable, Save};
+
+// The open buffers, one of which is shown at a time. While the list of them
+// is up, moving and Enter pick which.
+pub struct BufferList<B> {
+  bufs: Vec<B>,
+  current: usize,
+  v_size: Coord,
+  // The buffer selected in the list while it is shown.
+  picking: Option<usize>,
+}
+
+impl<B> BufferList<B> {
+  pub fn new(v_size: Coord, bufs: Vec<B>) -> Self {
+    BufferList {
+      bufs: bufs,
+      current: 0,
+      v_size: v_size,
+      picking: None,
+    }
+  }
+
+  pub fn len(&self) -> usize { self.bufs.len() }
+  pub fn is_empty(&self) -> bool { self.bufs.is_empty() }
+
+  // Panics if the list is empty.
+  pub fn current(&self) -> &B { &self.bufs[self.current] }
+  pub fn current_mut(&mut self) -> &mut B { &mut self.bufs[self.current] }
+
+  // The buffers are numbered in the order they were added.
+  pub fn current_index(&self) -> usize { self.current }
+  pub fn get(&self, i: usize) -> &B { &self.bufs[i] }
+  pub fn get_mut(&mut self, i: usize) -> &mut B { &mut self.bufs[i] }
+
+  pub fn select(&mut self, i: usize) {
+    if i < self.bufs.len() {
+      self.current = i;
+    }
+  }
+
+  // Adds `buf` at the end and switches to it. Buffers already in the list
+  // keep their numbers.
+  pub fn push(&mut self, buf: B) {
+    self.bufs.push(buf);
+    self.current = self.bufs.len() - 1;
+  }
+
+  pub fn next(&mut self) {
+    if !self.bufs.is_empty() {
+      self.current = (self.current + 1) % self.bufs.len();
+    }
+  }
+
+  pub fn prev(&mut self) {
+    if !self.bufs.is_empty() {
+      self.current = (self.current + self.bufs.len() - 1) % self.bufs.len();
+    }
+  }
+
+  // Removes the current buffer, switching to the one after it, or before it
+  // if it was last.
+  pub fn close(&mut self) -> Option<B> {
+    if self.bufs.is_empty() {
+      return None;
+    }
+    let buf = self.bufs.remove(self.current);
+    if self.current > 0 && self.current == self.bufs.len() {
+      self.current -= 1;
+    }
+    self.picking = None;
+    Some(buf)
+  }
+
+  pub fn picking(&self) -> bool { self.picking.is_some() }
+
+  // Shows the list with the current buffer selected.
+  pub fn show_list(&mut self) {
+    if !self.bufs.is_empty() {
+      self.picking = Some(self.current);
+    }
+  }
+
+  pub fn hide_list(&mut self) { self.picking = None; }
+}
+
+impl<B: Buffer + Save> BufferList<B> {
+  // The number of the buffer saving to `path`, if one is open.
+  pub fn find(&self, path: &Path) -> Option<usize> {
+    self.bufs.iter().position(|buf| buf.path() == Some(path))
+  }
+
+  // The names of the buffers with unsaved changes.
+  pub fn dirty(&self) -> Vec<String> {
+    self.bufs
+      .iter()
+      .filter(|buf| buf.is_dirty())
+      .map(|buf| buf.name().to_string())
+      .collect()
+  }
+}
+
+impl<B: Buffer + Save> Buffer for BufferList<B> {
+  fn name(&self) -> &str {
+    if self.bufs.is_empty() {
+      "-- no buffers --"
+    } else {
+      self.current().name()
+    }
+  }
+
+  fn status(&self) -> String {
+    match self.picking {
+      Some(_) => "*buffers*".to_string(),
+      None if self.bufs.is_empty() => String::new(),
+      None => self.current().status(),
+    }
+  }
+
+  fn paint(&self,
+           tbox: &mut Textbox,
+           at: Coord,
+           v_size: Coord,
+           view: (Coord, Coord),
+           active: bool) {
+    let selected = match self.picking {
+      Some(selected) => selected,
+      None => {
+        if !self.bufs.is_empty() {
+          self.current().paint(tbox, at, v_size, view, active);
+        }
+        return;
+      }
+    };
+
+    // The last row is the status line, and the rows above it list names,
+    // scrolled to keep the selection in view.
+    let rows = v_size.row();
+    if rows < 2 {
+      return;
+    }
+    let cols = v_size.col();
+    let first = (selected + 2).saturating_sub(rows);
+    let shown = cmp::min(self.bufs.len() - first, rows - 1);
+    for row in 0..shown {
+      let i = first + row;
+      let buf = &self.bufs[i];
+      let (fg, bg) = if i == selected {
+        (DEFAULT, DEFAULT | REVERSE)
+      } else {
+        (DEFAULT, DEFAULT)
+      };
+      for col in 0..cols {
+        tbox.set_cell(at + Coord(col, row), ' ', fg, bg);
+      }
+      let marker = if i == self.current { '>' } else { ' ' };
+      tbox.set_cell(at + row.to_row(), marker, fg, bg);
+      let dirty = if buf.is_dirty() { "*" } else { "" };
+      let name = format!("{}{}", buf.name(), dirty);
+      let name = clip(&name, cols.saturating_sub(2));
+      tbox.set_cells(at + Coord(2, row), name, fg, bg);
+    }
+    for col in 0..cols {
+      tbox.set_cell(at + Coord(col, rows - 1),
+                    ' ',
+                    DEFAULT,
+                    DEFAULT | REVERSE);
+    }
+    let status = format!("{} {}/{}", self.status(), selected + 1, self.len());
+    let status = clip(&status, cols.saturating_sub(2));
+    tbox.set_cells(at + Coord(2, rows - 1),
+                   status,
+                   DEFAULT,
+                   DEFAULT | REVERSE);
+    if active {
+      tbox.set_cursor(at + (selected - first).to_row());
+    }
+  }
+
+  fn view_size(&self) -> Coord { self.v_size }
+
+  // Every buffer is resized, not only the one shown.
+  fn set_view_size(&mut self, v_size: Coord) {
+    self.v_size = v_size;
+    for buf in self.bufs.iter_mut() {
+      buf.set_view_size(v_size);
+    }
+  }
+}
+
+impl<B: Save> Save for BufferList<B> {
+  fn save(&mut self) -> io::Result<usize> {
+    match self.picking {
+      Some(_) => Ok(0),
+      None => self.current_mut().save(),
+    }
+  }
+
+  fn save_as(&mut self, path: PathBuf) -> io::Result<usize> {
+    match self.picking {
+      Some(_) => Ok(0),
+      None => self.current_mut().save_as(path),
+    }
+  }
+
+  fn is_dirty(&self) -> bool {
+    !self.bufs.is_empty() && self.current().is_dirty()
+  }
+
+  fn path(&self) -> Option<&Path> {
+    if self.bufs.is_empty() {
+      None
+    } else {
+      self.current().path()
+    }
+  }
+
+  fn line_ending(&self) -> LineEnding {
+    if self.bufs.is_empty() {
+      LineEnding::Lf
+    } else {
+      self.current().line_ending()
+    }
+  }
+
+  fn mixed_line_endings(&self) -> bool {
+    !self.bufs.is_empty() && self.current().mixed_line_endings()
+  }
+
+  fn set_line_ending(&mut self, ending: LineEnding) {
+    if self.picking.is_none() {
+      self.current_mut().set_line_ending(ending);
+    }
+  }
+}
+
+impl<B: Navigable> Navigable for BufferList<B> {
+  fn cursor_up(&mut self) {
+    match self.picking {
+      Some(ref mut selected) => *selected = selected.saturating_sub(1),
+      None => self.bufs[self.current].cursor_up(),
+    }
+  }
+
+  fn cursor_down(&mut self) {
+    match self.picking {
+      Some(ref mut selected) => {
+        *selected = cmp::min(*selected + 1, self.bufs.len() - 1)
+      }
+      None => self.bufs[self.current].cursor_down(),
+    }
+  }
+
+  fn cursor_left(&mut self) {
+    if self.picking.is_none() {
+      self.current_mut().cursor_left();
+    }
+  }
+
+  fn cursor_right(&mut self) {
+    if self.picking.is_none() {
+      self.current_mut().cursor_right();
+    }
+  }
+
+  fn page_up(&mut self) {
+    match self.picking {
+      Some(ref mut selected) => *selected = 0,
+      None => self.bufs[self.current].page_up(),
+    }
+  }
+
+  fn page_down(&mut self) {
+    match self.picking {
+      Some(ref mut selected) => *selected = self.bufs.len() - 1,
+      None => self.bufs[self.current].page_down(),
+    }
+  }
+
+  fn home(&mut self) {
+    if self.picking.is_none() {
+      self.current_mut().home();
+    }
+  }
+
+  fn end(&mut self) {
+    if self.picking.is_none() {
+      self.current_mut().end();
+    }
+  }
+
+  fn goto_line(&mut self, line: usize) {
+    if self.picking.is_none() {
+      self.current_mut().goto_line(line);
+    }
+  }
+
+  fn cursor_to(&mut self, at: Coord) {
+    match self.picking {
+      Some(ref mut selected) => {
+        // The list scrolls the way `paint` draws it.
+        let rows = self.v_size.row();
+        let first = (*selected + 2).saturating_sub(rows);
+        if at.row() + 1 < rows && first + at.row() < self.bufs.len() {
+          *selected = first + at.row();
+        }
+      }
+      None => self.bufs[self.current].cursor_to(at),
+    }
+  }
+
+  fn scroll_up(&mut self, rows: usize) {
+    match self.picking {
+      Some(ref mut selected) => *selected = selected.saturating_sub(rows),
+      None => self.bufs[self.current].scroll_up(rows),
+    }
+  }
+
+  fn scroll_down(&mut self, rows: usize) {
+    match self.picking {
+      Some(ref mut selected) => {
+        *selected = cmp::min(*selected + rows, self.bufs.len() - 1)
+      }
+      None => self.bufs[self.current].scroll_down(rows),
+    }
+  }
+
+  fn view(&self) -> (Coord, Coord) { self.current().view() }
+
+  fn set_view(&mut self, view: (Coord, Coord)) {
+    self.current_mut().set_view(view)
+  }
+}
+
+impl<B: Editable> Editable for BufferList<B> {
+  // Enter in the list switches to the selected buffer.
+  fn insert(&mut self, ch: char) {
+    match self.picking {
+      Some(selected) => {
+        if ch == '\n' {
+          self.current = selected;
+          self.picking = None;
+        }
+      }
+      None => self.current_mut().insert(ch),
+    }
+  }
+
+  fn delete_line(&mut self) -> String {
+    match self.picking {
+      Some(_) => String::new(),
+      None => self.current_mut().delete_line(),
+    }
+  }
+
+  fn undo(&mut self) -> bool {
+    self.picking.is_none() && self.current_mut().undo()
+  }
+
+  fn redo(&mut self) -> bool {
+    self.picking.is_none() && self.current_mut().redo()
+  }
+}
@@ -0,0 +1,635 @@
+use regex::Regex;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf, MAIN_SEPARATOR};
+use text::clip;
+use textbox::*;
+use {Buffer, Editable, LineEnding, Navigable, Save, Searchable};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Mode {
+  Edit,
+  Find,
+  Goto,
+  Replace,
+  ReplaceWith,
+  ReplaceConfirm,
+  Open,
+  SaveAs,
+  SaveAsConfirm,
+  CloseConfirm,
+  QuitConfirm,
+}
+
+impl Mode {
+  pub fn is_edit(&self) -> bool { *self == Mode::Edit }
+  pub fn is_cmd(&self) -> bool { !self.is_edit() }
+}
+
+// What the owner of a command bar is asked to do by a finished command.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Request {
+  Open(PathBuf),
+  Close,
+  Quit,
+  SaveAllAndQuit,
+}
+
+pub struct CommandBar<B> {
+  prompt: String,
+  entry: String,
+  v_size: Coord,
+  buf: Box<B>,
+  mode: Mode,
+  search: Option<Regex>,
+  replace: String,
+  message: Option<String>,
+  request: Option<Request>,
+  // The path to save to once overwriting it is confirmed.
+  save_to: Option<PathBuf>,
+  // Whether closing an unnamed buffer is waiting on saving it as a path.
+  close_after_save: bool,
+}
+
+impl<B> CommandBar<B> {
+  pub fn new(v_size: Coord, buf: B) -> Self {
+    CommandBar {
+      prompt: ":".to_string(),
+      entry: String::new(),
+      v_size: v_size,
+      buf: Box::new(buf),
+      mode: Mode::Edit,
+      search: None,
+      replace: String::new(),
+      message: None,
+      request: None,
+      save_to: None,
+      close_after_save: false,
+    }
+  }
+
+  pub fn push_mode(&mut self, mode: Mode) {
+    if mode == Mode::Open || mode == Mode::SaveAs {
+      self.entry.clear();
+    }
+    self.mode = mode;
+    self.message = None;
+  }
+
+  // Takes the request made since last time, if any.
+  pub fn take_request(&mut self) -> Option<Request> { self.request.take() }
+
+  pub fn set_message(&mut self, message: &str) {
+    self.message = Some(message.to_string());
+  }
+
+  // Asks whether to save the buffers named in `dirty`, found by the owner of
+  // the bar, before quitting, or requests quitting if there are none.
+  pub fn confirm_quit(&mut self, dirty: &[String]) {
+    if dirty.is_empty() {
+      self.request = Some(Request::Quit);
+      return;
+    }
+    self.mode = Mode::QuitConfirm;
+    self.message = Some(if dirty.len() == 1 {
+      format!("{} is unsaved", dirty[0])
+    } else {
+      format!("{} unsaved: {}", dirty.len(), dirty.join(", "))
+    });
+  }
+
+  fn answer_quit(&mut self, ch: char) {
+    self.mode = Mode::Edit;
+    self.message = None;
+    self.request = match ch {
+      's' => Some(Request::SaveAllAndQuit),
+      'd' => Some(Request::Quit),
+      _ => None,
+    };
+  }
+}
+
+impl<B: Save> CommandBar<B> {
+  // Paths typed in open mode are relative to the directory of the buffer's
+  // file.
+  fn base_dir(&self) -> PathBuf {
+    self.buf
+      .path()
+      .and_then(|path| path.parent())
+      .map_or(PathBuf::new(), |dir| dir.to_path_buf())
+  }
+
+  // Completes the entry to the longest start shared by the names in its
+  // directory that begin with it, listing them if there are several.
+  fn complete(&mut self) {
+    let split = self.entry
+      .rfind(|ch| ch == '/' || ch == MAIN_SEPARATOR)
+      .map_or(0, |i| i + 1);
+    let dir = self.entry[..split].to_string();
+    let prefix = self.entry[split..].to_string();
+    let search = self.base_dir().join(&dir);
+    let search = if search.as_os_str().is_empty() {
+      Path::new(".").to_path_buf()
+    } else {
+      search
+    };
+    let entries = match fs::read_dir(&search) {
+      Ok(entries) => entries,
+      Err(_) => {
+        self.message = Some("no such directory".to_string());
+        return;
+      }
+    };
+    let mut names: Vec<String> = entries.filter_map(|entry| entry.ok())
+      .filter_map(|entry| {
+        let is_dir = entry.path().is_dir();
+        entry.file_name().into_string().ok().map(|mut name| {
+          if is_dir {
+            name.push(MAIN_SEPARATOR);
+          }
+          name
+        })
+      })
+      // Hidden files only complete once a '.' is typed.
+      .filter(|name| {
+        name.starts_with(&prefix) &&
+        (prefix.starts_with('.') || !name.starts_with('.'))
+      })
+      .collect();
+    if names.is_empty() {
+      self.message = Some("no match".to_string());
+      return;
+    }
+    names.sort();
+
+    let mut common = names[0].clone();
+    for name in names.iter().skip(1) {
+      let len = common.chars()
+        .zip(name.chars())
+        .take_while(|&(a, b)| a == b)
+        .map(|(a, _)| a.len_utf8())
+        .sum();
+      common.truncate(len);
+    }
+    self.message = if names.len() > 1 {
+      Some(names.join(" "))
+    } else {
+      None
+    };
+    self.entry = format!("{}{}", dir, common);
+  }
+
+  // Asks whether to save the buffer before closing it, or requests closing
+  // it if there are no changes to lose.
+  pub fn confirm_close(&mut self) {
+    if self.buf.is_dirty() {
+      self.mode = Mode::CloseConfirm;
+      self.message = None;
+    } else {
+      self.request = Some(Request::Close);
+    }
+  }
+
+  fn answer_close(&mut self, ch: char) {
+    self.mode = Mode::Edit;
+    match ch {
+      's' => {
+        // A buffer without a file goes on to ask for one instead, and is
+        // closed once saved to it.
+        match self.save() {
+          Ok(_) if self.mode == Mode::SaveAs => self.close_after_save = true,
+          Ok(_) if !self.buf.is_dirty() => self.request = Some(Request::Close),
+          Ok(_) => (),
+          Err(err) => self.message = Some(err.to_string()),
+        }
+      }
+      'd' => self.request = Some(Request::Close),
+      _ => (),
+    }
+  }
+
+  // Saves to the path entered in save-as mode, asking first if that would
+  // overwrite another file.
+  fn save_as_entry(&mut self) {
+    if self.entry.is_empty() {
+      return;
+    }
+    let path = self.base_dir().join(&self.entry);
+    self.entry.clear();
+    if path.exists() && self.buf.path() != Some(path.as_path()) {
+      self.save_to = Some(path);
+      self.mode = Mode::SaveAsConfirm;
+    } else {
+      self.write(path);
+    }
+  }
+
+  fn confirm_overwrite(&mut self, ch: char) {
+    match (ch, self.save_to.take()) {
+      ('y', Some(path)) => self.write(path),
+      _ => {
+        self.mode = Mode::Edit;
+        self.message = Some("not saved".to_string());
+        self.close_after_save = false;
+      }
+    }
+  }
+
+  fn write(&mut self, path: PathBuf) {
+    self.mode = Mode::Edit;
+    self.message = match self.buf.save_as(path) {
+      Ok(_) => {
+        if self.close_after_save {
+          self.request = Some(Request::Close);
+        }
+        None
+      }
+      Err(err) => Some(err.to_string()),
+    };
+    self.close_after_save = false;
+  }
+}
+
+impl<B: Searchable> CommandBar<B> {
+  pub fn pop_mode(&mut self) {
+    if self.mode.is_edit() {
+      // Escape from edit mode drops the search and its highlighting.
+      self.search = None;
+      self.buf.set_search(None);
+    }
+    self.close_after_save = false;
+    self.mode = match self.mode {
+      Mode::Edit => Mode::Edit,
+      Mode::Find => Mode::Edit,
+      Mode::Goto => Mode::Edit,
+      Mode::Replace => Mode::Edit,
+      Mode::ReplaceWith => Mode::Edit,
+      Mode::ReplaceConfirm => Mode::Edit,
+      Mode::Open => Mode::Edit,
+      Mode::SaveAs => Mode::Edit,
+      Mode::SaveAsConfirm => Mode::Edit,
+      Mode::CloseConfirm => Mode::Edit,
+      Mode::QuitConfirm => Mode::Edit,
+    };
+    self.save_to = None;
+    self.message = None;
+  }
+
+  // Compiles the entry as the search pattern unless it is empty or unchanged.
+  fn compile_search(&mut self) -> bool {
+    let stale = match self.search {
+      Some(ref re) => !self.entry.is_empty() && re.as_str() != self.entry,
+      None => !self.entry.is_empty(),
+    };
+    if stale {
+      match Regex::new(&self.entry) {
+        Ok(re) => {
+          self.buf.set_search(Some(re.clone()));
+          self.search = Some(re);
+        }
+        Err(_) => {
+          self.search = None;
+          self.buf.set_search(None);
+          self.message = Some("invalid regex".to_string());
+          return false;
+        }
+      }
+    }
+    self.search.is_some()
+  }
+
+  fn find(&mut self) {
+    if self.compile_search() {
+      self.find_next();
+    }
+  }
+
+  // Starts replace mode, or says why the buffer can't be replaced in.
+  pub fn start_replace(&mut self) {
+    if self.buf.can_replace() {
+      self.push_mode(Mode::Replace);
+    } else {
+      self.message = Some("can't replace in this buffer".to_string());
+    }
+  }
+
+  fn replace(&mut self, ch: char) {
+    match self.mode {
+      Mode::Replace => {
+        if self.compile_search() {
+          self.entry.clear();
+          self.mode = Mode::ReplaceWith;
+        }
+      }
+      Mode::ReplaceWith => {
+        self.replace = self.entry.clone();
+        self.entry.clear();
+        self.find_next();
+        self.mode = match self.message {
+          Some(_) => Mode::Edit,
+          None => Mode::ReplaceConfirm,
+        };
+      }
+      Mode::ReplaceConfirm => {
+        let re = match self.search {
+          Some(ref re) => re,
+          None => return,
+        };
+        match ch {
+          'y' | '\n' => {
+            if !self.buf.replace(re, &self.replace) {
+              self.message = Some("no more matches".to_string());
+              self.mode = Mode::Edit;
+            }
+          }
+          'n' => {
+            if !self.buf.find_next(re) {
+              self.message = Some("no more matches".to_string());
+              self.mode = Mode::Edit;
+            }
+          }
+          'a' => {
+            let count = self.buf.replace_all(re, &self.replace);
+            self.message = Some(format!("replaced {}", count));
+            self.mode = Mode::Edit;
+          }
+          _ => (),
+        }
+      }
+      _ => (),
+    }
+  }
+
+  pub fn find_next(&mut self) {
+    self.message = match self.search {
+      Some(ref re) if !self.buf.find_next(re) => Some("no match".to_string()),
+      _ => None,
+    };
+  }
+}
+
+impl<B: Buffer> Buffer for CommandBar<B> {
+  fn name(&self) -> &str { self.buf.name() }
+  fn status(&self) -> String {
+    let mode = match self.mode {
+      Mode::Edit => "*edit*",
+      Mode::Find => "*find*",
+      Mode::Goto => "*goto*",
+      Mode::Replace => "*replace*",
+      Mode::ReplaceWith => "*replace with*",
+      Mode::ReplaceConfirm => "*replace? y/n/a*",
+      Mode::Open => "*open*",
+      Mode::SaveAs => "*save as*",
+      Mode::SaveAsConfirm => "*overwrite? y/n*",
+      Mode::CloseConfirm => "*save before closing? s/d/c*",
+      Mode::QuitConfirm => "*save before quitting? s/d/c*",
+    };
+    match self.message {
+      Some(ref message) => format!("{} {}", mode, message),
+      None => mode.to_string(),
+    }
+  }
+
+  // `v_size` takes in the status line and command line, as `view_size`
+  // does.
+  fn paint(&self,
+           tbox: &mut Textbox,
+           at: Coord,
+           v_size: Coord,
+           view: (Coord, Coord),
+           active: bool) {
+    let rows = v_size.row().saturating_sub(1 + self.v_size.row());
+    let buf_size = Coord(v_size.col(), rows);
+    self.buf.paint(tbox, at, buf_size, view, active & self.mode.is_edit());
+    let status_at = at + rows.to_row();
+    let at = status_at + 1.to_row();
+
+    // Everything is clipped to the width of the view.
+    let cols = v_size.col();
+    let status = self.buf.status();
+    let status = clip(&status, cols.saturating_sub(2));
+    for col in 0..cols {
+      tbox.set_cell(status_at + col.to_col(), ' ', DEFAULT, DEFAULT | REVERSE);
+    }
+    let col = cols.saturating_sub(2 + str_width(status));
+    tbox.set_cells(status_at + col.to_col(),
+                   status,
+                   DEFAULT,
+                   DEFAULT | REVERSE);
+    let status = self.status();
+    let status = clip(&status, cols.saturating_sub(2));
+    tbox.set_cells(status_at + 2.to_col(), status, DEFAULT, DEFAULT | REVERSE);
+
+    if active & self.mode.is_cmd() {
+      let prompt = clip(&self.prompt, cols);
+      let width = tbox.set_cells(at, prompt, DEFAULT, DEFAULT);
+      let at = at + width.to_col() + 1.to_col();
+      let entry = clip(&self.entry, cols.saturating_sub(width + 2));
+      let width = tbox.set_cells(at, entry, DEFAULT, DEFAULT);
+      tbox.set_cursor(at + width.to_col());
+    }
+  }
+
+  // The buffer, the status line and the command line.
+  fn view_size(&self) -> Coord {
+    let rows = self.buf.view_size().row() + 1 + self.v_size.row();
+    Coord(self.v_size.col(), rows)
+  }
+
+  fn set_view_size(&mut self, v_size: Coord) {
+    let rows = v_size.row().saturating_sub(1 + self.v_size.row());
+    self.v_size.0 = v_size.col();
+    self.buf.set_view_size(Coord(v_size.col(), rows));
+  }
+}
+
+impl<B: Editable + Navigable + Save + Searchable> Editable for CommandBar<B> {
+  fn insert(&mut self, ch: char) {
+    if self.mode.is_edit() {
+      self.buf.insert(ch);
+    } else if self.mode == Mode::ReplaceConfirm {
+      self.replace(ch);
+    } else if self.mode == Mode::SaveAsConfirm {
+      self.confirm_overwrite(ch);
+    } else if self.mode == Mode::CloseConfirm {
+      self.answer_close(ch);
+    } else if self.mode == Mode::QuitConfirm {
+      self.answer_quit(ch);
+    } else {
+      match ch {
+        '\n' => {
+          match self.mode {
+            Mode::Goto => {
+              if let Ok(line) = self.entry.trim().parse::<usize>() {
+                self.buf.goto_line(if line > 0 { line - 1 } else { 0 });
+              }
+              self.entry.clear();
+              self.mode = Mode::Edit;
+            }
+            // Stay in find mode so repeated enters step through matches.
+            Mode::Find => self.find(),
+            Mode::Replace | Mode::ReplaceWith => self.replace(ch),
+            // Paths that don't exist yet open as new files.
+            Mode::Open => {
+              if !self.entry.is_empty() {
+                let path = self.base_dir().join(&self.entry);
+                self.request = Some(Request::Open(path));
+              }
+              self.entry.clear();
+              self.mode = Mode::Edit;
+            }
+            Mode::SaveAs => self.save_as_entry(),
+            _ => (),
+          }
+        }
+        '\t' if self.mode == Mode::Open || self.mode == Mode::SaveAs => {
+          self.complete()
+        }
+        '\x08' => {
+          // backspace
+          if self.entry.len() > 0 {
+            self.entry.pop();
+          }
+          self.message = None;
+        }
+        '\x7f' => (), // delete - ignore
+        _ => {
+          self.entry.push(ch);
+          self.message = None;
+        }
+      }
+    }
+  }
+
+  fn delete_line(&mut self) -> String {
+    if self.mode.is_edit() {
+      self.buf.delete_line()
+    } else {
+      let out = self.entry.to_string();
+      self.entry.clear();
+      out
+    }
+  }
+
+  fn undo(&mut self) -> bool {
+    let done = self.mode.is_edit() && self.buf.undo();
+    self.message = if done {
+      None
+    } else {
+      Some("nothing to undo".to_string())
+    };
+    done
+  }
+
+  fn redo(&mut self) -> bool {
+    let done = self.mode.is_edit() && self.buf.redo();
+    self.message = if done {
+      None
+    } else {
+      Some("nothing to redo".to_string())
+    };
+    done
+  }
+}
+
+impl<B: Buffer + Navigable> Navigable for CommandBar<B> {
+  fn cursor_up(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.cursor_up();
+    }
+  }
+
+  fn cursor_down(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.cursor_down();
+    }
+  }
+
+  fn cursor_left(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.cursor_left();
+    }
+  }
+
+  fn cursor_right(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.cursor_right();
+    }
+  }
+
+  fn page_up(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.page_up();
+    }
+  }
+
+  fn page_down(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.page_down();
+    }
+  }
+
+  fn home(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.home();
+    }
+  }
+
+  fn end(&mut self) {
+    if self.mode.is_edit() {
+      self.buf.end();
+    }
+  }
+
+  fn goto_line(&mut self, line: usize) {
+    if self.mode.is_edit() {
+      self.buf.goto_line(line);
+    }
+  }
+
+  fn cursor_to(&mut self, at: Coord) {
+    // Clicks on the status line and prompt are ignored.
+    if self.mode.is_edit() && at.row() < self.buf.view_size().row() {
+      self.buf.cursor_to(at);
+    }
+  }
+
+  fn scroll_up(&mut self, rows: usize) {
+    if self.mode.is_edit() {
+      self.buf.scroll_up(rows);
+    }
+  }
+
+  fn scroll_down(&mut self, rows: usize) {
+    if self.mode.is_edit() {
+      self.buf.scroll_down(rows);
+    }
+  }
+
+  fn view(&self) -> (Coord, Coord) { self.buf.view() }
+  fn set_view(&mut self, view: (Coord, Coord)) { self.buf.set_view(view) }
+}
+
+impl<B: Save> Save for CommandBar<B> {
+  // A buffer without a file asks for a path to save to.
+  fn save(&mut self) -> io::Result<usize> {
+    if self.buf.path().is_none() {
+      self.push_mode(Mode::SaveAs);
+      Ok(0)
+    } else {
+      self.buf.save()
+    }
+  }
+
+  fn save_as(&mut self, path: PathBuf) -> io::Result<usize> {
+    self.buf.save_as(path)
+  }
+  fn is_dirty(&self) -> bool { self.buf.is_dirty() }
+  fn path(&self) -> Option<&Path> { self.buf.path() }
+  fn line_ending(&self) -> LineEnding { self.buf.line_ending() }
+  fn mixed_line_endings(&self) -> bool { self.buf.mixed_line_endings() }
+
+  fn set_line_ending(&mut self, ending: LineEnding) {
+    self.buf.set_line_ending(ending)
+  }
+}
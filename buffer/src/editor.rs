@@ -0,0 +1,227 @@
+// The editor that `view` runs: the key bindings and the event loop that
+// drives the windows, kept here so tests can script it with a MemoryTextbox.
+
+use buffer_list::BufferList;
+use command_bar::{CommandBar, Mode, Request};
+use std::io;
+use textbox::*;
+use window::{Split, Windows};
+use {Buffer, Editable, LineEnding, Navigable, Save, Searchable};
+
+// The number of rows scrolled by one notch of the mouse wheel.
+const WHEEL_ROWS: usize = 3;
+
+// Handles the keys and mouse wheel for the focused buffer.
+fn edit<B>(bufs: &mut BufferList<CommandBar<B>>, e: Event)
+  where B: Buffer + Editable + Navigable + Save + Searchable
+{
+  match e {
+    Event::Key(_, CTRL, Key::Char('B')) => bufs.show_list(),
+    Event::Key(_, NO_MODS, Key::Escape) if bufs.picking() => bufs.hide_list(),
+    // Other commands wait until the list is closed.
+    Event::Key(_, CTRL, _) if bufs.picking() => (),
+    Event::Key(_, CTRL, Key::Char('N')) => bufs.next(),
+    Event::Key(_, CTRL, Key::Char('P')) => bufs.prev(),
+    Event::Key(_, NO_MODS, Key::Escape) => bufs.current_mut().pop_mode(),
+    Event::Key(_, CTRL, Key::Char('S')) => {
+      if let Err(err) = bufs.save() {
+        bufs.current_mut().set_message(&err.to_string());
+      }
+    }
+    // Converts the line endings between LF and CRLF.
+    Event::Key(_, CTRL, Key::Char('E')) => {
+      let ending = match bufs.line_ending() {
+        LineEnding::Lf => LineEnding::CrLf,
+        LineEnding::CrLf => LineEnding::Lf,
+      };
+      bufs.set_line_ending(ending);
+      let message = format!("line endings: {}", ending.name());
+      bufs.current_mut().set_message(&message);
+    }
+    Event::Key(_, CTRL, Key::Char('G')) => {
+      bufs.current_mut().push_mode(Mode::Goto)
+    }
+    Event::Key(_, CTRL, Key::Char('F')) => {
+      bufs.current_mut().push_mode(Mode::Find)
+    }
+    Event::Key(_, NO_MODS, Key::F(3)) => bufs.current_mut().find_next(),
+    Event::Key(_, CTRL, Key::Char('R')) => {
+      bufs.current_mut().start_replace()
+    }
+    Event::Key(_, CTRL, Key::Char('A')) => {
+      bufs.current_mut().push_mode(Mode::SaveAs)
+    }
+    Event::Key(_, CTRL, Key::Char('O')) => {
+      bufs.current_mut().push_mode(Mode::Open)
+    }
+    Event::Key(_, CTRL, Key::Char('Z')) => {
+      bufs.undo();
+    }
+    Event::Key(_, CTRL, Key::Char('Y')) => {
+      bufs.redo();
+    }
+    Event::Key(_, CTRL, Key::Char('X')) => {
+      bufs.delete_line();
+    }
+    Event::Key(_, NO_MODS, Key::Up) |
+    Event::Key(_, CTRL, Key::Char('K')) => bufs.cursor_up(),
+    Event::Key(_, NO_MODS, Key::Down) |
+    Event::Key(_, CTRL, Key::Char('J')) => bufs.cursor_down(),
+    Event::Key(_, NO_MODS, Key::Left) |
+    Event::Key(_, CTRL, Key::Char('H')) => bufs.cursor_left(),
+    Event::Key(_, NO_MODS, Key::Right) |
+    Event::Key(_, CTRL, Key::Char('L')) => bufs.cursor_right(),
+    Event::Key(_, NO_MODS, Key::PageUp) |
+    Event::Key(_, CTRL_SHIFT, Key::Char('K')) => bufs.page_up(),
+    Event::Key(_, NO_MODS, Key::PageDown) |
+    Event::Key(_, CTRL_SHIFT, Key::Char('J')) => bufs.page_down(),
+    Event::Key(_, NO_MODS, Key::Home) |
+    Event::Key(_, CTRL_SHIFT, Key::Char('H')) => bufs.home(),
+    Event::Key(_, NO_MODS, Key::End) |
+    Event::Key(_, CTRL_SHIFT, Key::Char('L')) => bufs.end(),
+    // Event::Key('/', ALT, _) => {
+    //   println!("divide");
+    //   bufs.insert(0xf7 as char);
+    // }
+    Event::Key(ch, NO_MODS, Key::Char(_)) |
+    Event::Key(ch, SHIFT, Key::Char(_)) => bufs.insert(ch),
+    Event::Key(_, NO_MODS, Key::Enter) => bufs.insert('\n'),
+    Event::Key(_, NO_MODS, Key::Backspace) => bufs.insert('\x08'),
+    Event::Key(_, NO_MODS, Key::Delete) => bufs.insert('\x7f'),
+    Event::Key(_, NO_MODS, Key::Tab) => bufs.insert('\t'),
+    Event::Mouse(Button::WheelUp, _, _) => bufs.scroll_up(WHEEL_ROWS),
+    Event::Mouse(Button::WheelDown, _, _) => bufs.scroll_down(WHEEL_ROWS),
+    _ => (),
+  }
+}
+
+// Saves every buffer with changes, stopping at the first that needs a name
+// or can't be saved, which is switched to. Returns whether all were saved.
+fn save_all<B: Save>(bufs: &mut BufferList<CommandBar<B>>) -> bool {
+  for i in 0..bufs.len() {
+    let buf = bufs.get_mut(i);
+    if !buf.is_dirty() {
+      continue;
+    }
+    if let Err(err) = buf.save() {
+      buf.set_message(&err.to_string());
+    }
+    if buf.is_dirty() {
+      bufs.select(i);
+      return false;
+    }
+  }
+  true
+}
+
+// Puts `buf` under a command bar, warning that saving will change the line
+// endings of a file read with both kinds.
+fn command_bar<B: Save>(width: usize, buf: B) -> CommandBar<B> {
+  let mut cmd = CommandBar::new(Coord(width, 1), buf);
+  if cmd.mixed_line_endings() {
+    let message = format!("mixed line endings, saving as {}",
+                          cmd.line_ending().name());
+    cmd.set_message(&message);
+  }
+  cmd
+}
+
+// Edits `files` until quit, with `open` making the buffer for a file of the
+// given view size, or a scratch buffer without one.
+pub fn run<B, F>(tbox: &mut Textbox, files: &[&str], open: F)
+  where B: Buffer + Editable + Navigable + Save + Searchable,
+        F: Fn(Coord, Option<&str>) -> io::Result<B>
+{
+  let size = tbox.size();
+  let mut bufs = vec![];
+  let mut errors = vec![];
+  for &file in files {
+    match open(size - 2.to_row(), Some(file)) {
+      Ok(buf) => bufs.push(buf),
+      Err(err) => errors.push(format!("{}: {}", file, err)),
+    }
+  }
+  // Without files to open, start with a scratch buffer.
+  if bufs.is_empty() {
+    bufs.push(open(size - 2.to_row(), None)
+      .expect("scratch buffers don't read files"));
+  }
+  let bufs = bufs.into_iter()
+    .map(|buf| command_bar(size.col(), buf))
+    .collect();
+  let mut bufs = BufferList::new(size, bufs);
+  // Files that couldn't be read are left out and named in the message.
+  if !errors.is_empty() {
+    bufs.current_mut().set_message(&errors.join(", "));
+  }
+  let mut win = Windows::new(size, bufs);
+  win.paint(tbox);
+  tbox.present();
+
+  loop {
+    if let Some(e) = tbox.pop_event() {
+      match e {
+        // The list is closed first since it takes the keys that would
+        // answer the prompt.
+        Event::Key(_, CTRL, Key::Char('Q')) => {
+          win.bufs_mut().hide_list();
+          let dirty = win.bufs().dirty();
+          win.bufs_mut().current_mut().confirm_quit(&dirty);
+        }
+        Event::Resize(size) => win.set_size(size),
+        Event::Key(_, ALT, Key::Char('S')) => win.split(Split::Horizontal),
+        Event::Key(_, ALT, Key::Char('V')) => win.split(Split::Vertical),
+        Event::Key(_, ALT, Key::Char('O')) => win.focus_next(),
+        Event::Key(_, ALT, Key::Char('W')) => {
+          win.close_pane();
+        }
+        Event::Key(_, ALT, Key::Char('=')) => win.resize_pane(1),
+        Event::Key(_, ALT, Key::Char('-')) => win.resize_pane(-1),
+        Event::Key(_, CTRL, Key::Char('W')) if !win.bufs().picking() => {
+          win.bufs_mut().current_mut().confirm_close()
+        }
+        Event::Mouse(Button::Left, _, at) => {
+          if let Some(at) = win.focus_at(at) {
+            win.bufs_mut().cursor_to(at);
+          }
+        }
+        e => edit(win.bufs_mut(), e),
+      }
+    }
+
+    match win.bufs_mut().current_mut().take_request() {
+      // Switch to the file entered in open mode if it is already open.
+      Some(Request::Open(path)) => {
+        match win.bufs().find(&path) {
+          Some(i) => win.bufs_mut().select(i),
+          None => {
+            let size = tbox.size();
+            match open(size - 2.to_row(), Some(&path.to_string_lossy())) {
+              Ok(buf) => win.open(command_bar(size.col(), buf)),
+              Err(err) => {
+                win.bufs_mut().current_mut().set_message(&err.to_string())
+              }
+            }
+          }
+        }
+      }
+      Some(Request::Close) => {
+        win.close_buffer();
+        if win.bufs().is_empty() {
+          break;
+        }
+      }
+      Some(Request::Quit) => break,
+      Some(Request::SaveAllAndQuit) => {
+        if save_all(win.bufs_mut()) {
+          break;
+        }
+      }
+      None => (),
+    }
+
+    tbox.clear();
+    win.paint(tbox);
+    tbox.present();
+  }
+}
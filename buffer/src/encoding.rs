@@ -0,0 +1,92 @@
+use std::str;
+
+// The chars Windows-1252 gives the bytes 0x80 to 0x9f, where Latin-1 has
+// control codes. The five bytes it leaves undefined keep their control codes
+// so that every byte still reads and writes back unchanged.
+const WINDOWS_1252: [char; 32] =
+  ['\u{20ac}', '\u{81}', '\u{201a}', '\u{192}', '\u{201e}', '\u{2026}',
+   '\u{2020}', '\u{2021}', '\u{2c6}', '\u{2030}', '\u{160}', '\u{2039}',
+   '\u{152}', '\u{8d}', '\u{17d}', '\u{8f}', '\u{90}', '\u{2018}',
+   '\u{2019}', '\u{201c}', '\u{201d}', '\u{2022}', '\u{2013}', '\u{2014}',
+   '\u{2dc}', '\u{2122}', '\u{161}', '\u{203a}', '\u{153}', '\u{9d}',
+   '\u{17e}', '\u{178}'];
+
+// How far into a file to look for the zero bytes that mark it as binary.
+const BINARY_SNIFF: usize = 8000;
+
+/// The encodings files are read in and saved back to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Encoding {
+  Utf8,
+  Latin1,
+  Windows1252,
+}
+
+impl Encoding {
+  // Files that aren't valid UTF-8 are taken as Latin-1, or Windows-1252 if
+  // they use any of the bytes where the two differ. Both decode every byte.
+  pub fn detect(bytes: &[u8]) -> Encoding {
+    if str::from_utf8(bytes).is_ok() {
+      Encoding::Utf8
+    } else if bytes.iter().any(|&b| 0x80 <= b && b < 0xa0) {
+      Encoding::Windows1252
+    } else {
+      Encoding::Latin1
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      Encoding::Utf8 => "UTF-8",
+      Encoding::Latin1 => "Latin-1",
+      Encoding::Windows1252 => "Windows-1252",
+    }
+  }
+
+  // Invalid UTF-8 becomes U+FFFD, but `detect` only picks UTF-8 for bytes
+  // without any.
+  pub fn decode(&self, bytes: &[u8]) -> String {
+    match *self {
+      Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+      Encoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+      Encoding::Windows1252 => {
+        bytes.iter()
+          .map(|&b| if 0x80 <= b && b < 0xa0 {
+            WINDOWS_1252[b as usize - 0x80]
+          } else {
+            b as char
+          })
+          .collect()
+      }
+    }
+  }
+
+  // Appends `s` to `out`, or returns the first char the encoding has no byte
+  // for.
+  pub fn encode(&self, s: &str, out: &mut Vec<u8>) -> Result<(), char> {
+    if *self == Encoding::Utf8 {
+      out.extend_from_slice(s.as_bytes());
+      return Ok(());
+    }
+    for ch in s.chars() {
+      let c = ch as u32;
+      let byte = if c < 0x80 || (0xa0 <= c && c < 0x100) {
+        Some(c as u8)
+      } else if *self == Encoding::Latin1 {
+        if c < 0xa0 { Some(c as u8) } else { None }
+      } else {
+        WINDOWS_1252.iter().position(|&w| w == ch).map(|i| 0x80 + i as u8)
+      };
+      match byte {
+        Some(byte) => out.push(byte),
+        None => return Err(ch),
+      }
+    }
+    Ok(())
+  }
+}
+
+// Text files don't hold zero bytes, except in UTF-16, which isn't read.
+pub fn is_binary(bytes: &[u8]) -> bool {
+  bytes.iter().take(BINARY_SNIFF).any(|&b| b == 0)
+}
@@ -0,0 +1,970 @@
+use atomic::write_file;
+use encoding::{is_binary, Encoding};
+use hex_edit::hex_dump;
+use regex::Regex;
+use highlight::{highlighter_for, token_style};
+use ropey::{Rope, RopeBuilder};
+use std::cell::RefCell;
+use std::cmp;
+use std::fs;
+use std::io;
+use std::io::{Error, ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use text::{byte_to_col, col_str, col_to_byte, col_to_x, col_width, cols,
+           glyph, x_to_col, Text};
+use textbox::*;
+use unicode_segmentation::UnicodeSegmentation;
+use {Buffer, Editable, Highlighter, LineEnding, Navigable, Save, Searchable,
+     Span, State};
+
+// Why a binary file can't be changed, and how to edit it instead.
+const READ_ONLY: &'static str = "read-only, open with --hex to edit";
+
+// A single edit to the text, with its position given as Coord(byte, row).
+// Bytes rather than columns, since typing a combining char changes which
+// column the bytes after it are in. Either kind of text may span lines with
+// embedded '\n's.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Change {
+  Insert(Coord, String),
+  Delete(Coord, String),
+}
+
+impl Change {
+  fn invert(&self) -> Change {
+    match *self {
+      Change::Insert(at, ref text) => Change::Delete(at, text.clone()),
+      Change::Delete(at, ref text) => Change::Insert(at, text.clone()),
+    }
+  }
+}
+
+// An undo step with the (offset, cursor) views before and after it.
+#[derive(Clone, Debug)]
+struct Step {
+  changes: Vec<Change>,
+  before: (Coord, Coord),
+  after: (Coord, Coord),
+}
+
+// `saved` is how many undo steps there were when the buffer was last saved,
+// or None once that state can't be undone or redone back to.
+#[derive(Clone, Debug)]
+struct History {
+  undo: Vec<Step>,
+  redo: Vec<Step>,
+  saved: Option<usize>,
+}
+
+pub struct FileEdit {
+  path: Option<PathBuf>,
+  text: Rope,
+  offset: Coord,
+  cursor: Coord,
+  v_size: Coord,
+  search: Option<Regex>,
+  history: History,
+  tab_width: usize,
+  hard_tabs: bool,
+  // Whether saving keeps the previous contents of the file in `path~`.
+  backup: bool,
+  // How the file was laid out, so saving writes it back the same way.
+  line_ending: LineEnding,
+  saved_ending: LineEnding,
+  mixed_endings: bool,
+  final_newline: bool,
+  bom: bool,
+  encoding: Encoding,
+  // Binary files are shown as a hex dump that can't be edited or saved.
+  read_only: bool,
+  highlighter: Option<Box<Highlighter>>,
+  // The highlighter's state at the start of each row, as far as painted.
+  states: RefCell<Vec<State>>,
+}
+
+impl FileEdit {
+  // An unnamed scratch buffer, which is given a path when first saved.
+  pub fn new(v_size: Coord) -> Self {
+    FileEdit::with_text(v_size, None, RopeBuilder::new().finish(), false)
+  }
+
+  // The line ending used by most lines is kept, as are a missing newline at
+  // the end of the file, a byte order mark at the start and the encoding.
+  // A file that doesn't exist yet opens empty and is created by saving.
+  pub fn from_file(v_size: Coord, filename: &str) -> io::Result<Self> {
+    let path = PathBuf::from(filename);
+    let bytes = match fs::read(&path) {
+      Ok(bytes) => bytes,
+      Err(ref err) if err.kind() == ErrorKind::NotFound => vec![],
+      Err(err) => return Err(err),
+    };
+    // Binary files are only shown here, laid out as `HexEdit` paints them,
+    // and are edited by opening them with `--hex`.
+    if is_binary(&bytes) {
+      let text = Rope::from_str(&hex_dump(&bytes));
+      let mut buf = FileEdit::with_text(v_size, Some(path), text, false);
+      buf.highlighter = None;
+      buf.read_only = true;
+      return Ok(buf);
+    }
+
+    let encoding = Encoding::detect(&bytes);
+    let mut contents = encoding.decode(&bytes);
+    let bom = contents.starts_with('\u{feff}');
+    if bom {
+      contents.remove(0);
+    }
+    let mut text = RopeBuilder::new();
+    let mut hard_tabs = false;
+    let (mut lf, mut crlf) = (0, 0);
+    let mut final_newline = true;
+    let mut lines = contents.split('\n').peekable();
+    let mut row = 0;
+    while let Some(line) = lines.next() {
+      let last = lines.peek().is_none();
+      if last && line.is_empty() && row > 0 {
+        break;
+      }
+      if row > 0 {
+        text.append("\n");
+      }
+      let line = if last {
+        final_newline = bytes.is_empty();
+        line
+      } else if line.ends_with('\r') {
+        crlf += 1;
+        &line[..line.len() - 1]
+      } else {
+        lf += 1;
+        line
+      };
+      // Keep indenting with tabs in files already indented with them.
+      hard_tabs |= line.starts_with('\t');
+      text.append(line);
+      row += 1;
+    }
+
+    let mut buf = FileEdit::with_text(v_size, Some(path), text.finish(),
+                                      hard_tabs);
+    if crlf > lf {
+      buf.line_ending = LineEnding::CrLf;
+      buf.saved_ending = LineEnding::CrLf;
+    }
+    buf.mixed_endings = lf > 0 && crlf > 0;
+    buf.final_newline = final_newline;
+    buf.bom = bom;
+    buf.encoding = encoding;
+    Ok(buf)
+  }
+
+  fn with_text(v_size: Coord,
+               path: Option<PathBuf>,
+               text: Rope,
+               hard_tabs: bool)
+               -> Self {
+    let highlighter = path.as_ref().and_then(|path| highlighter_for(path));
+    FileEdit {
+      path: path,
+      text: text,
+      offset: zero(),
+      cursor: zero(),
+      v_size: v_size,
+      search: None,
+      history: History {
+        undo: vec![],
+        redo: vec![],
+        saved: Some(0),
+      },
+      tab_width: 2,
+      hard_tabs: hard_tabs,
+      backup: false,
+      line_ending: LineEnding::Lf,
+      saved_ending: LineEnding::Lf,
+      mixed_endings: false,
+      final_newline: true,
+      bom: false,
+      encoding: Encoding::Utf8,
+      read_only: false,
+      highlighter: highlighter,
+      states: RefCell::new(vec![0]),
+    }
+  }
+
+  // Sets how far apart tab stops are, which is also the number of spaces the
+  // tab key inserts when not using hard tabs.
+  pub fn set_tab_width(&mut self, tab_width: usize) {
+    self.tab_width = cmp::max(1, tab_width);
+  }
+
+  // Sets whether the tab key inserts a '\t' rather than spaces.
+  pub fn set_hard_tabs(&mut self, hard_tabs: bool) {
+    self.hard_tabs = hard_tabs;
+  }
+
+  pub fn set_backup(&mut self, backup: bool) { self.backup = backup; }
+
+  pub fn encoding(&self) -> Encoding { self.encoding }
+  pub fn is_read_only(&self) -> bool { self.read_only }
+
+  // Replaces the highlighter picked by the file's extension.
+  pub fn set_highlighter(&mut self, highlighter: Option<Box<Highlighter>>) {
+    self.highlighter = highlighter;
+    self.states.get_mut().truncate(1);
+  }
+
+  // The number of columns in `row`.
+  fn row_cols(&self, row: usize) -> usize { cols(&self.text.row(row)) }
+
+  fn mark_saved(&mut self) {
+    self.history.saved = Some(self.history.undo.len());
+    self.saved_ending = self.line_ending;
+    self.mixed_endings = false;
+  }
+
+  // Converts a column position into the byte position used by `Text`.
+  fn byte_at(&self, at: Coord) -> Coord {
+    Coord(col_to_byte(&self.text.row(at.row()), at.col()), at.row())
+  }
+
+  fn apply(&mut self, change: &Change) {
+    match *change {
+      Change::Insert(at, ref text) => self.text.insert_at(at, text),
+      Change::Delete(at, ref text) => self.text.remove_at(at, text.len()),
+    }
+    // Rows after the change may start in a different state.
+    let at = match *change {
+      Change::Insert(at, _) | Change::Delete(at, _) => at,
+    };
+    self.states.get_mut().truncate(at.row() + 1);
+  }
+
+  // The highlighted spans of `line`, which is `row`. The states of the rows
+  // above it are brought up to date first, so only rows not highlighted
+  // since the last edit above them are highlighted again.
+  fn spans(&self, row: usize, line: &str) -> Vec<Span> {
+    let highlighter = match self.highlighter {
+      Some(ref highlighter) => highlighter,
+      None => return vec![],
+    };
+    let mut states = self.states.borrow_mut();
+    while states.len() <= row {
+      let prev = states.len() - 1;
+      let state = highlighter.highlight(&self.text.row(prev), states[prev]).1;
+      states.push(state);
+    }
+    let (spans, state) = highlighter.highlight(line, states[row]);
+    if states.len() == row + 1 {
+      states.push(state);
+    }
+    spans
+  }
+
+  // Applies `changes` as a single undo step. Runs of typed characters are
+  // merged into the previous step so they undo together.
+  fn edit(&mut self, changes: Vec<Change>) {
+    for change in changes.iter() {
+      self.apply(change);
+    }
+    self.history.redo.clear();
+    let steps = self.history.undo.len();
+    if self.history.saved.map_or(false, |saved| saved > steps) {
+      self.history.saved = None;
+    }
+
+    // Typing onto the saved step would leave no way back to the saved text.
+    let saved = self.history.saved == Some(steps);
+    let view = (self.offset, self.cursor);
+    if changes.len() == 1 {
+      if let Some(step) = self.history.undo.last_mut() {
+        let merged = match (step.changes.last_mut(), &changes[0]) {
+          (Some(&mut Change::Insert(at, ref mut typed)),
+           &Change::Insert(next, ref text)) => {
+            let typing = !text.contains('\n') && !typed.contains('\n');
+            if typing && !saved && next == at + typed.len().to_col() {
+              typed.push_str(text);
+              true
+            } else {
+              false
+            }
+          }
+          _ => false,
+        };
+        if merged {
+          return;
+        }
+      }
+    }
+    self.history.undo.push(Step {
+      changes: changes,
+      before: view,
+      after: view,
+    });
+  }
+
+  fn restore(&mut self, view: (Coord, Coord)) {
+    self.offset = view.0;
+    self.cursor = view.1;
+  }
+
+  // Moves the cursor to the first match of `re` at or after the byte
+  // `start_at` on `row_at`, wrapping around the end of the buffer back to it.
+  fn seek(&mut self, re: &Regex, row_at: usize, start_at: usize) -> bool {
+    let rows = self.text.rows();
+    for i in 0..rows + 1 {
+      let row = (row_at + i) % rows;
+      let found = {
+        let line = self.text.row(row);
+        let start = if i == 0 { start_at } else { 0 };
+        if start > line.len() {
+          None
+        } else {
+          match re.find_at(&line, start) {
+            Some(m) if i < rows || m.start() < start_at => {
+              Some(byte_to_col(&line, m.start()))
+            }
+            _ => None,
+          }
+        }
+      };
+      if let Some(col) = found {
+        self.move_to(col, row);
+        return true;
+      }
+    }
+    false
+  }
+
+  // Writes every row in the buffer's encoding, each followed by the line
+  // ending.
+  fn write_rows(&self, out: &mut Write) -> io::Result<usize> {
+    let mut written = 0;
+    if self.bom {
+      try!(out.write_all("\u{feff}".as_bytes()));
+      written += 3;
+    }
+    let nl = self.line_ending.as_str();
+    let rows = self.text.rows();
+    let mut bytes = vec![];
+    for row in 0..rows {
+      bytes.clear();
+      if let Err(ch) = self.encoding.encode(&self.text.row(row), &mut bytes) {
+        let message = format!("{:?} can't be saved as {}",
+                              ch,
+                              self.encoding.name());
+        return Err(Error::new(ErrorKind::InvalidData, message));
+      }
+      if row + 1 < rows || self.final_newline {
+        bytes.extend_from_slice(nl.as_bytes());
+      }
+      try!(out.write_all(&bytes));
+      written += bytes.len();
+    }
+    Ok(written)
+  }
+
+  fn move_to(&mut self, col: usize, row: usize) {
+    self.goto_line(row);
+    self.move_to_col(col);
+  }
+
+  // The screen columns, measured from the start of the line, where the view
+  // starts and where the cursor is. All rows scroll together by the cursor
+  // row's offset, moved just enough to keep the cursor in view. A view kept
+  // by another pane may be past the end of text since cut, so its cursor
+  // row is kept within the text.
+  fn scroll(&self, cols: usize, view: (Coord, Coord)) -> (usize, usize) {
+    let (offset, cursor) = view;
+    let row_at = cmp::min(offset.row() + cursor.row(), self.text.rows() - 1);
+    let col_at = offset.col() + cursor.col();
+    let line = self.text.row(row_at);
+    let mut scroll = col_to_x(&line, offset.col(), self.tab_width);
+    let x_at = col_to_x(&line, col_at, self.tab_width);
+    let width = col_width(col_str(&line, col_at), x_at, self.tab_width);
+    let width = cmp::max(1, width);
+    if x_at < scroll {
+      scroll = x_at;
+    } else if x_at + width > scroll + cols {
+      scroll = cmp::min(x_at, x_at + width - cols);
+    }
+    (scroll, x_at)
+  }
+
+  // Moves the cursor to `col`, scrolling only if it is out of view.
+  fn move_to_col(&mut self, col: usize) {
+    let view_cols = self.v_size.col();
+    if col < self.offset.col() {
+      self.offset.0 = col;
+      self.cursor.0 = 0;
+    } else if col < self.offset.col() + view_cols {
+      self.cursor.0 = col - self.offset.col();
+    } else {
+      self.offset.0 = col + 1 - view_cols;
+      self.cursor.0 = view_cols - 1;
+    }
+  }
+}
+
+impl Save for FileEdit {
+  fn save(&mut self) -> io::Result<usize> {
+    if self.is_dirty() {
+      if let Some(ref path) = self.path {
+        let written = try!(write_file(path, self.backup, |out| {
+          self.write_rows(out)
+        }));
+        self.mark_saved();
+        Ok(written)
+      } else {
+        Err(Error::new(ErrorKind::NotFound, "no filename given"))
+      }
+    } else {
+      Ok(0)
+    }
+  }
+
+  // The highlighter is picked again for the new file's extension.
+  fn save_as(&mut self, path: PathBuf) -> io::Result<usize> {
+    if self.read_only {
+      return Err(Error::new(ErrorKind::PermissionDenied, READ_ONLY));
+    }
+    let written = try!(write_file(&path, self.backup, |out| {
+      self.write_rows(out)
+    }));
+    self.set_highlighter(highlighter_for(&path));
+    self.path = Some(path);
+    self.mark_saved();
+    Ok(written)
+  }
+
+  // Undoing back to the saved text and line ending makes a buffer clean.
+  fn is_dirty(&self) -> bool {
+    self.history.saved != Some(self.history.undo.len()) ||
+    self.line_ending != self.saved_ending
+  }
+
+  fn path(&self) -> Option<&Path> { self.path.as_ref().map(|p| p.as_path()) }
+  fn line_ending(&self) -> LineEnding { self.line_ending }
+  fn mixed_line_endings(&self) -> bool { self.mixed_endings }
+
+  fn set_line_ending(&mut self, ending: LineEnding) {
+    if ending != self.line_ending && !self.read_only {
+      self.line_ending = ending;
+      self.mixed_endings = false;
+    }
+  }
+}
+
+impl Buffer for FileEdit {
+  fn name(&self) -> &str {
+    match self.path {
+      Some(ref path) => path.to_str().unwrap(),
+      None => "-- buffer --",
+    }
+  }
+
+  fn paint(&self,
+           tbox: &mut Textbox,
+           global: Coord,
+           v_size: Coord,
+           view: (Coord, Coord),
+           active: bool) {
+    let (offset, cursor) = view;
+    let (scroll, x_at) = self.scroll(v_size.col(), view);
+    if active {
+      tbox.set_cursor(global + Coord(x_at - scroll, cursor.row()));
+    }
+    let row_at = offset.row() + cursor.row();
+    let col_at = offset.col() + cursor.col();
+    let x_end = scroll + v_size.col();
+    for row in 0..v_size.row() {
+      if offset.row() + row >= self.text.rows() {
+        break;
+      }
+      let line = self.text.row(offset.row() + row);
+      let matches: Vec<(usize, usize)> = match self.search {
+        Some(ref re) => {
+          re.find_iter(&line).map(|m| (m.start(), m.end())).collect()
+        }
+        None => vec![],
+      };
+      let spans = self.spans(offset.row() + row, &line);
+      let current = if offset.row() + row == row_at {
+        Some(col_to_byte(&line, col_at))
+      } else {
+        None
+      };
+      // let mut initial_spaces = true;
+      let mut x = 0;
+      for (i, g) in line.grapheme_indices(true) {
+        let width = col_width(g, x, self.tab_width);
+        if x >= x_end {
+          break;
+          // } else if ch == ' ' {
+          //   tbox.set_cell(Coord(col, row), 183 as char, BRIGHT | DEFAULT, DEFAULT);
+        } else if x >= scroll && x + width <= x_end {
+          // initial_spaces = false;
+          let fg = match matches.iter().find(|&&(s, e)| s <= i && i < e) {
+            Some(&(s, _)) if Some(s) == current => CYAN | REVERSE,
+            Some(_) => YELLOW | REVERSE,
+            None => {
+              spans.iter()
+                .find(|span| span.start <= i && i < span.end)
+                .map_or(DEFAULT, |span| token_style(span.token))
+            }
+          };
+          if g == "\t" {
+            for col in x..x + width {
+              let at = global + Coord(col - scroll, row);
+              tbox.set_cell(at, ' ', fg, DEFAULT);
+            }
+          } else {
+            // A cell holds one char, so only the first of a cluster is drawn.
+            let ch = glyph(g.chars().next().unwrap());
+            tbox.set_char(global + Coord(x - scroll, row), ch, fg, DEFAULT);
+          }
+        }
+        x += width;
+      }
+    }
+  }
+
+  fn status(&self) -> String {
+    let curr_col = 1 + self.offset.0 + self.cursor.0;
+    let curr_row = 1 + self.offset.1 + self.cursor.1;
+    let rows_in_buf = self.text.rows();
+    let cols_in_row = //if curr_row < rows_in_buf {
+      self.row_cols(self.offset.1 + self.cursor.1);
+    // } else {
+    // 0
+    // };
+    format!(// "{} - {:2}/{:2} - {:3}/{:3}",
+            "{}{} - {}/{} - {}/{}{}{}",
+            self.name(),
+            if self.is_dirty() { "*" } else { "" },
+            curr_col,
+            cols_in_row,
+            curr_row,
+            rows_in_buf,
+            // A file read with both endings says so until it is converted
+            // or saved.
+            match (self.line_ending, self.mixed_endings) {
+              (LineEnding::Lf, false) => "",
+              (LineEnding::CrLf, false) => " - CRLF",
+              (LineEnding::Lf, true) => " - LF (mixed)",
+              (LineEnding::CrLf, true) => " - CRLF (mixed)",
+            },
+            match (self.read_only, self.encoding) {
+              (true, _) => format!(" - binary, {}", READ_ONLY),
+              (false, Encoding::Utf8) => String::new(),
+              (false, encoding) => format!(" - {}", encoding.name()),
+            })
+  }
+
+  fn view_size(&self) -> Coord { self.v_size }
+
+  fn set_view_size(&mut self, v_size: Coord) {
+    // The cursor needs at least one cell to be in.
+    self.v_size = Coord(cmp::max(1, v_size.col()), cmp::max(1, v_size.row()));
+    if self.cursor.row() >= self.v_size.row() {
+      self.offset.1 += self.cursor.row() + 1 - self.v_size.row();
+      self.cursor.1 = self.v_size.row() - 1;
+    }
+    if self.cursor.col() >= self.v_size.col() {
+      self.offset.0 += self.cursor.col() + 1 - self.v_size.col();
+      self.cursor.0 = self.v_size.col() - 1;
+    }
+  }
+}
+
+impl Navigable for FileEdit {
+  fn cursor_up(&mut self) {
+    if self.offset.row() + self.cursor.row() == 0 {
+      // do nothing
+    } else if self.cursor.row() == 0 {
+      self.offset.1 -= 1;
+    } else {
+      self.cursor.1 -= 1;
+    }
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn cursor_down(&mut self) {
+    if self.offset.row() + self.cursor.row() >= self.text.rows() - 1 {
+      // do nothing
+    } else if self.cursor.row() >= self.v_size.row() - 1 {
+      self.cursor.1 = self.v_size.row() - 1;
+      self.offset.1 += 1;
+    } else {
+      self.cursor.1 += 1;
+    }
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn cursor_left(&mut self) {
+    if self.offset.0 + self.cursor.0 == 0 {
+      if self.offset.1 + self.cursor.1 > 0 {
+        self.cursor_up();
+        self.end();
+      }
+    } else if self.cursor.0 == 0 {
+      self.offset.0 -= 1;
+    } else {
+      self.cursor.0 -= 1;
+    }
+  }
+
+  fn cursor_right(&mut self) {
+    let offset_row = self.offset.1;
+    let cursor_row = self.cursor.1;
+    let view_cols = self.v_size.0;
+    let line_len = self.row_cols(offset_row + cursor_row);
+
+    if self.offset.0 + self.cursor.0 >= line_len {
+      if offset_row + cursor_row < self.text.rows() - 1 {
+        self.cursor_down();
+        self.home();
+      } else {
+        self.end();
+      }
+    } else if self.cursor.0 >= view_cols - 1 {
+      self.offset.0 += 1;
+      self.cursor.0 = view_cols - 1;
+    } else {
+      self.cursor.0 += 1;
+    }
+  }
+
+  fn page_up(&mut self) {
+    if self.offset.row() + self.cursor.row() == 0 {
+      // do nothing
+    } else if self.offset.1 == 0 {
+      self.cursor.1 = 0;
+    } else if self.offset.row() <= self.v_size.row() - 1 {
+      if self.cursor.row() > self.v_size.row() - self.offset.row() {
+        self.cursor.1 -= self.v_size.row() - self.offset.row();
+      }
+      self.offset.1 = 0;
+    } else {
+      self.offset.1 -= self.v_size.row();
+    }
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn page_down(&mut self) {
+    if self.offset.1 + self.cursor.1 >= self.text.rows() - 1 {
+      // do nothing
+    } else if self.text.rows() < self.v_size.1 {
+      self.cursor.1 = self.text.rows() - 1;
+    } else if self.offset.1 >= self.text.rows() - self.v_size.1 {
+      self.cursor.1 = self.text.rows() - self.offset.1 - 1;
+    } else {
+      self.offset.1 += self.v_size.1;
+      if self.offset.1 + self.v_size.1 >= self.text.rows() - 1 {
+        self.offset.1 = self.text.rows() - self.v_size.1;
+      }
+    }
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn home(&mut self) {
+    self.offset.0 = 0;
+    self.cursor.0 = 0;
+  }
+
+  fn end(&mut self) {
+    let offset_row = self.offset.1;
+    let cursor_row = self.cursor.1;
+    let view_cols = self.v_size.0;
+    let line_len = self.row_cols(offset_row + cursor_row);
+
+    if view_cols >= line_len {
+      self.offset.0 = 0;
+      self.cursor.0 = line_len;
+    } else {
+      self.offset.0 = line_len + 1 - view_cols;
+      self.cursor.0 = view_cols - 1;
+    }
+  }
+
+  fn goto_line(&mut self, line: usize) {
+    let len = self.text.rows();
+    let line = if line >= len { len - 1 } else { line };
+    if line < self.v_size.1 {
+      self.offset.1 = 0;
+      self.cursor.1 = line;
+    } else {
+      self.offset.1 = line - self.cursor.1;
+    }
+  }
+
+  fn cursor_to(&mut self, at: Coord) {
+    let row = cmp::min(self.offset.row() + at.row(), self.text.rows() - 1);
+    let (scroll, _) = self.scroll(self.v_size.col(), self.view());
+    let col = x_to_col(&self.text.row(row), scroll + at.col(), self.tab_width);
+    self.cursor.1 = row - self.offset.row();
+    self.move_to_col(col);
+  }
+
+  fn scroll_up(&mut self, rows: usize) {
+    let rows = cmp::min(rows, self.offset.row());
+    self.offset.1 -= rows;
+    self.cursor.1 = cmp::min(self.cursor.row() + rows, self.v_size.row() - 1);
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn scroll_down(&mut self, rows: usize) {
+    let last = self.text.rows().saturating_sub(self.v_size.row());
+    let rows = cmp::min(rows, last.saturating_sub(self.offset.row()));
+    self.offset.1 += rows;
+    self.cursor.1 = self.cursor.row().saturating_sub(rows);
+
+    if self.offset.0 + self.cursor.0 >=
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+
+  fn view(&self) -> (Coord, Coord) { (self.offset, self.cursor) }
+
+  fn set_view(&mut self, view: (Coord, Coord)) {
+    self.restore(view);
+    let last = self.text.rows() - 1;
+    if self.offset.row() > last {
+      self.offset.1 = last;
+      self.cursor.1 = 0;
+    } else if self.offset.row() + self.cursor.row() > last {
+      self.cursor.1 = last - self.offset.row();
+    }
+    if self.offset.0 + self.cursor.0 >
+       self.row_cols(self.offset.1 + self.cursor.1) {
+      self.end();
+    }
+  }
+}
+
+impl Editable for FileEdit {
+  fn insert(&mut self, ch: char) {
+    use std::cmp::min;
+
+    if self.read_only {
+      return;
+    }
+    let col_at = self.offset.col() + self.cursor.col();
+    let line_cols = self.row_cols(self.offset.row() + self.cursor.row());
+    let row_at = self.offset.row() + self.cursor.row();
+    match ch {
+      '\n' => {
+        let at = self.byte_at(Coord(min(col_at, line_cols), row_at));
+        self.edit(vec![Change::Insert(at, "\n".to_string())]);
+        self.cursor_down();
+        self.home();
+      }
+      '\x08' => {
+        // backspace
+        let curr_row = self.offset.1 + self.cursor.1;
+        let curr_col = self.offset.0 + self.cursor.0;
+        if curr_col == 0 {
+          if curr_row > 0 {
+            // join lines
+            let prev_row = curr_row - 1;
+            let prev_len = self.row_cols(prev_row);
+            let at = self.byte_at(Coord(prev_len, prev_row));
+            self.edit(vec![Change::Delete(at, "\n".to_string())]);
+            self.cursor_up();
+            self.move_to_col(prev_len);
+          }
+        } else {
+          let at = Coord(curr_col - 1, curr_row);
+          let prev = col_str(&self.text.row(curr_row), at.col()).to_string();
+          let at = self.byte_at(at);
+          self.edit(vec![Change::Delete(at, prev)]);
+          self.cursor_left();
+        }
+      }
+      '\t' if !self.hard_tabs => {
+        let line = self.text.row(row_at).into_owned();
+        let x = col_to_x(&line, col_at, self.tab_width);
+        for _ in 0..self.tab_width - x % self.tab_width {
+          self.insert(' ');
+        }
+      }
+      '\x7f' => {
+        // delete
+        let curr_row = self.offset.1 + self.cursor.1;
+        let curr_col = self.offset.0 + self.cursor.0;
+        let line_len = self.row_cols(curr_row);
+        if curr_col == line_len {
+          if curr_row < self.text.rows() - 1 {
+            // join lines
+            let at = self.byte_at(Coord(curr_col, curr_row));
+            self.edit(vec![Change::Delete(at, "\n".to_string())]);
+          }
+        } else {
+          let next = col_str(&self.text.row(curr_row), curr_col).to_string();
+          let at = self.byte_at(Coord(curr_col, curr_row));
+          self.edit(vec![Change::Delete(at, next)]);
+        }
+      }
+      _ => {
+        let at = self.byte_at(Coord(col_at, row_at));
+        self.edit(vec![Change::Insert(at, ch.to_string())]);
+        let byte = at.col() + ch.len_utf8();
+        // A combining char joins the column before it, so the cursor does not
+        // always step right.
+        let col = byte_to_col(&self.text.row(row_at), byte);
+        self.move_to_col(col);
+      }
+    }
+  }
+
+  fn delete_line(&mut self) -> String {
+    if self.read_only {
+      return String::new();
+    }
+    let curr_row = self.offset.1 + self.cursor.1;
+    let curr_col = self.offset.0 + self.cursor.0;
+    let curr_str = self.text.row(curr_row).into_owned();
+    let change = if curr_row + 1 < self.text.rows() {
+      Change::Delete(Coord(0, curr_row), curr_str.clone() + "\n")
+    } else if curr_row > 0 {
+      let prev_len = self.text.row(curr_row - 1).len();
+      let text = "\n".to_string() + &curr_str;
+      Change::Delete(Coord(prev_len, curr_row - 1), text)
+    } else {
+      Change::Delete(Coord(0, curr_row), curr_str.clone())
+    };
+    self.edit(vec![change]);
+    if curr_row >= self.text.rows() {
+      self.cursor_up();
+    }
+    let curr_row = self.offset.1 + self.cursor.1;
+    if curr_col >= self.row_cols(curr_row) {
+      self.end();
+    }
+    curr_str
+  }
+
+  fn undo(&mut self) -> bool {
+    match self.history.undo.pop() {
+      Some(mut step) => {
+        for change in step.changes.iter().rev() {
+          self.apply(&change.invert());
+        }
+        step.after = (self.offset, self.cursor);
+        self.restore(step.before);
+        self.history.redo.push(step);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn redo(&mut self) -> bool {
+    match self.history.redo.pop() {
+      Some(step) => {
+        for change in step.changes.iter() {
+          self.apply(change);
+        }
+        self.restore(step.after);
+        self.history.undo.push(step);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl Searchable for FileEdit {
+  fn find_next(&mut self, re: &Regex) -> bool {
+    let row_at = self.offset.row() + self.cursor.row();
+    let col_at = self.offset.col() + self.cursor.col();
+    let start = col_to_byte(&self.text.row(row_at), col_at + 1);
+    self.seek(re, row_at, start)
+  }
+
+  fn set_search(&mut self, re: Option<Regex>) { self.search = re; }
+
+  fn replace(&mut self, re: &Regex, with: &str) -> bool {
+    if self.read_only {
+      return false;
+    }
+    let row_at = self.offset.row() + self.cursor.row();
+    let col_at = self.offset.col() + self.cursor.col();
+    let start = col_to_byte(&self.text.row(row_at), col_at);
+    let replaced = {
+      let line = self.text.row(row_at);
+      re.captures_iter(&line)
+        .find(|caps| caps.get(0).map_or(false, |m| m.start() == start))
+        .map(|caps| {
+          let mut out = String::new();
+          caps.expand(with, &mut out);
+          (caps[0].to_string(), out)
+        })
+    };
+    match replaced {
+      Some((found, out)) => {
+        let at = Coord(start, row_at);
+        let mut end = start + out.len();
+        let empty = found.is_empty();
+        self.edit(vec![Change::Delete(at, found), Change::Insert(at, out)]);
+        if empty {
+          // An empty match would be found again where it was replaced, so
+          // the search goes on from the next grapheme, or the next row.
+          let line = self.text.row(row_at);
+          end += line[end..].graphemes(true).next().map_or(1, |g| g.len());
+        }
+        self.seek(re, row_at, end)
+      }
+      None => self.seek(re, row_at, start),
+    }
+  }
+
+  fn replace_all(&mut self, re: &Regex, with: &str) -> usize {
+    if self.read_only {
+      return 0;
+    }
+    let mut count = 0;
+    let mut changes = vec![];
+    for row in 0..self.text.rows() {
+      let line = self.text.row(row);
+      let found = re.find_iter(&line).count();
+      if found > 0 {
+        let out = re.replace_all(&line, with).into_owned();
+        changes.push(Change::Delete(Coord(0, row), line.into_owned()));
+        changes.push(Change::Insert(Coord(0, row), out));
+        count += found;
+      }
+    }
+    if count > 0 {
+      // Replacing the whole batch is a single undo step.
+      self.edit(changes);
+      let row_at = self.offset.row() + self.cursor.row();
+      let col_at = self.offset.col() + self.cursor.col();
+      if col_at > self.row_cols(row_at) {
+        self.end();
+      }
+    }
+    count
+  }
+
+  fn can_replace(&self) -> bool { !self.read_only }
+}
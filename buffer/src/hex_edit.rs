@@ -0,0 +1,545 @@
+use atomic::write_file;
+use regex::Regex;
+use std::cmp;
+use std::fs;
+use std::io;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+use textbox::*;
+use {Buffer, Editable, LineEnding, Navigable, Save, Searchable};
+
+// Each row shows the offset of its first byte, then the bytes in hex, then
+// the bytes again as ASCII. `hex_dump` lays out binary files opened in
+// `FileEdit` the same way.
+const ROW_BYTES: usize = 16;
+const HEX_X: usize = 10;
+const ASCII_X: usize = HEX_X + 3 * ROW_BYTES + 1;
+
+// Lays out `bytes` in the rows `HexEdit` paints, for showing binary files
+// read-only in `FileEdit`.
+pub fn hex_dump(bytes: &[u8]) -> String {
+  let mut out = String::new();
+  for (row, chunk) in bytes.chunks(ROW_BYTES).enumerate() {
+    if row > 0 {
+      out.push('\n');
+    }
+    let start = out.len();
+    out.push_str(&format!("{:08x}", row * ROW_BYTES));
+    for (col, &byte) in chunk.iter().enumerate() {
+      pad(&mut out, start + HEX_X + 3 * col);
+      out.push_str(&format!("{:02x}", byte));
+    }
+    pad(&mut out, start + ASCII_X);
+    out.extend(chunk.iter().map(|&byte| printable(byte)));
+  }
+  out
+}
+
+fn pad(out: &mut String, len: usize) {
+  while out.len() < len {
+    out.push(' ');
+  }
+}
+
+// How a byte is shown in the ASCII column.
+fn printable(byte: u8) -> char {
+  if 0x20 <= byte && byte < 0x7f { byte as char } else { '.' }
+}
+
+// One byte overwritten, for undo, with the digit the cursor was on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Patch {
+  at: usize,
+  low: bool,
+  before: u8,
+  after: u8,
+}
+
+// Edits the bytes of a file by overwriting them, one hex digit at a time or
+// as ASCII chars. The file keeps its length.
+pub struct HexEdit {
+  path: Option<PathBuf>,
+  bytes: Vec<u8>,
+  // The first row in view.
+  top: usize,
+  // The byte under the cursor, and whether the cursor is on its low digit.
+  at: usize,
+  low: bool,
+  // Whether typing overwrites from the ASCII column rather than the hex.
+  ascii: bool,
+  v_size: Coord,
+  // How many patches deep the undo list was when last saved, or None if
+  // that state was undone and then patched over.
+  saved: Option<usize>,
+  backup: bool,
+  // Highlighted where it matches the rows in view, which are searched as
+  // they are painted rather than the whole file after every patch.
+  search: Option<Regex>,
+  undo: Vec<Patch>,
+  redo: Vec<Patch>,
+}
+
+impl HexEdit {
+  // An unnamed, empty scratch buffer, which is given a path when first saved.
+  pub fn new(v_size: Coord) -> Self {
+    HexEdit::with_bytes(v_size, None, vec![])
+  }
+
+  // A file that doesn't exist yet opens empty and is created by saving.
+  pub fn from_file(v_size: Coord, filename: &str) -> io::Result<Self> {
+    let path = PathBuf::from(filename);
+    let bytes = match fs::read(&path) {
+      Ok(bytes) => bytes,
+      Err(ref err) if err.kind() == ErrorKind::NotFound => vec![],
+      Err(err) => return Err(err),
+    };
+    Ok(HexEdit::with_bytes(v_size, Some(path), bytes))
+  }
+
+  fn with_bytes(v_size: Coord,
+                path: Option<PathBuf>,
+                bytes: Vec<u8>)
+                -> Self {
+    let mut buf = HexEdit {
+      path: path,
+      bytes: bytes,
+      top: 0,
+      at: 0,
+      low: false,
+      ascii: false,
+      v_size: zero(),
+      saved: Some(0),
+      backup: false,
+      search: None,
+      undo: vec![],
+      redo: vec![],
+    };
+    buf.set_view_size(v_size);
+    buf
+  }
+
+  pub fn set_backup(&mut self, backup: bool) { self.backup = backup; }
+
+  pub fn bytes(&self) -> &[u8] { &self.bytes }
+
+  fn rows(&self) -> usize {
+    cmp::max(1, (self.bytes.len() + ROW_BYTES - 1) / ROW_BYTES)
+  }
+
+  // The last byte the cursor can be on.
+  fn last(&self) -> usize { self.bytes.len().saturating_sub(1) }
+
+  // Moves the cursor to byte `at`, scrolling only if it is out of view.
+  fn move_to(&mut self, at: usize) {
+    self.at = cmp::min(at, self.last());
+    let row = self.at / ROW_BYTES;
+    if row < self.top {
+      self.top = row;
+    } else if row >= self.top + self.v_size.row() {
+      self.top = row + 1 - self.v_size.row();
+    }
+  }
+
+  fn patch(&mut self, patch: Patch) {
+    self.bytes[patch.at] = patch.after;
+    self.move_to(patch.at);
+    self.low = patch.low;
+  }
+
+  fn overwrite(&mut self, byte: u8) {
+    let patch = Patch {
+      at: self.at,
+      low: self.low,
+      before: self.bytes[self.at],
+      after: byte,
+    };
+    self.patch(patch);
+    if self.saved.map_or(false, |saved| saved > self.undo.len()) {
+      self.saved = None;
+    }
+    self.undo.push(patch);
+    self.redo.clear();
+  }
+
+  // The matches of `search` in `rows` rows from `top`, and a row either side
+  // so that short matches across the edges of the view are whole.
+  fn matches_in_view(&self, top: usize, rows: usize) -> Vec<(usize, usize)> {
+    let re = match self.search {
+      Some(ref re) => re,
+      None => return vec![],
+    };
+    let first = top.saturating_sub(1) * ROW_BYTES;
+    let end = (top + rows + 1) * ROW_BYTES;
+    let end = cmp::min(end, self.bytes.len());
+    matches(&self.bytes[first..end], re)
+      .into_iter()
+      .map(|(s, e)| (first + s, first + e))
+      .collect()
+  }
+
+  fn write_bytes(&self, path: &Path) -> io::Result<usize> {
+    write_file(path, self.backup, |out| {
+      try!(out.write_all(&self.bytes));
+      Ok(self.bytes.len())
+    })
+  }
+}
+
+// The ranges of bytes matching `re`, which is run over the bytes read as
+// Latin-1 so that every byte is one char.
+fn matches(bytes: &[u8], re: &Regex) -> Vec<(usize, usize)> {
+  let text: String = bytes.iter().map(|&b| b as char).collect();
+  let mut starts: Vec<usize> = text.char_indices().map(|(i, _)| i).collect();
+  starts.push(text.len());
+  re.find_iter(&text)
+    .map(|m| {
+      (starts.binary_search(&m.start()).unwrap_or(0),
+       starts.binary_search(&m.end()).unwrap_or(0))
+    })
+    .collect()
+}
+
+// The first byte of the first match of `re` in `bytes`, if any.
+fn find(bytes: &[u8], re: &Regex) -> Option<usize> {
+  let text: String = bytes.iter().map(|&b| b as char).collect();
+  re.find(&text).map(|m| text[..m.start()].chars().count())
+}
+
+// The match in `matches` that byte `i` is in, if any.
+fn match_at(matches: &[(usize, usize)], i: usize) -> Option<(usize, usize)> {
+  let after = match matches.binary_search_by(|&(s, _)| s.cmp(&i)) {
+    Ok(found) => return Some(matches[found]),
+    Err(after) => after,
+  };
+  match after.checked_sub(1).map(|prev| matches[prev]) {
+    Some((s, e)) if i < e => Some((s, e)),
+    _ => None,
+  }
+}
+
+// Draws `text` from column `x` of the row at `at`, leaving out what is past
+// the `cols` of the view.
+fn put(tbox: &mut Textbox, at: Coord, cols: usize, x: usize, text: &str,
+       fg: Style) {
+  for (i, ch) in text.chars().enumerate() {
+    if x + i < cols {
+      tbox.set_cell(at + (x + i).to_col(), ch, fg, DEFAULT);
+    }
+  }
+}
+
+impl Save for HexEdit {
+  fn save(&mut self) -> io::Result<usize> {
+    if self.is_dirty() {
+      if let Some(ref path) = self.path {
+        let written = try!(self.write_bytes(path));
+        self.saved = Some(self.undo.len());
+        Ok(written)
+      } else {
+        Err(Error::new(ErrorKind::NotFound, "no filename given"))
+      }
+    } else {
+      Ok(0)
+    }
+  }
+
+  fn save_as(&mut self, path: PathBuf) -> io::Result<usize> {
+    let written = try!(self.write_bytes(&path));
+    self.path = Some(path);
+    self.saved = Some(self.undo.len());
+    Ok(written)
+  }
+
+  fn is_dirty(&self) -> bool { self.saved != Some(self.undo.len()) }
+  fn path(&self) -> Option<&Path> { self.path.as_ref().map(|p| p.as_path()) }
+
+  // The bytes are saved as they are, without lines to convert.
+  fn line_ending(&self) -> LineEnding { LineEnding::Lf }
+  fn mixed_line_endings(&self) -> bool { false }
+  fn set_line_ending(&mut self, _: LineEnding) {}
+}
+
+impl Buffer for HexEdit {
+  fn name(&self) -> &str {
+    match self.path {
+      Some(ref path) => path.to_str().unwrap(),
+      None => "-- buffer --",
+    }
+  }
+
+  // The view's offset gives the top row, and its cursor the row and hex
+  // digit, as `view` lays them out.
+  fn paint(&self,
+           tbox: &mut Textbox,
+           global: Coord,
+           v_size: Coord,
+           view: (Coord, Coord),
+           active: bool) {
+    let (offset, cursor) = view;
+    let top = offset.row();
+    let cols = v_size.col();
+    let matches = self.matches_in_view(top, v_size.row());
+    let byte_at = (top + cursor.row()) * ROW_BYTES + cursor.col() / 2;
+    for row in 0..v_size.row() {
+      let first = (top + row) * ROW_BYTES;
+      if first >= self.bytes.len() {
+        break;
+      }
+      let at = global + row.to_row();
+      put(tbox, at, cols, 0, &format!("{:08x}", first), DEFAULT);
+      let end = cmp::min(first + ROW_BYTES, self.bytes.len());
+      for (col, i) in (first..end).enumerate() {
+        let fg = match match_at(&matches, i) {
+          Some((s, _)) if s == byte_at => CYAN | REVERSE,
+          Some(_) => YELLOW | REVERSE,
+          None => DEFAULT,
+        };
+        let byte = self.bytes[i];
+        put(tbox, at, cols, HEX_X + 3 * col, &format!("{:02x}", byte), fg);
+        put(tbox, at, cols, ASCII_X + col, &printable(byte).to_string(), fg);
+      }
+    }
+
+    if active {
+      let col = byte_at % ROW_BYTES;
+      let x = if self.ascii {
+        ASCII_X + col
+      } else {
+        HEX_X + 3 * col + cursor.col() % 2
+      };
+      let x = cmp::min(x, cols.saturating_sub(1));
+      tbox.set_cursor(global + Coord(x, cursor.row()));
+    }
+  }
+
+  fn status(&self) -> String {
+    format!("{}{} - {:x}/{:x}{}",
+            self.name(),
+            if self.is_dirty() { "*" } else { "" },
+            self.at,
+            self.bytes.len(),
+            if self.ascii { " - ascii" } else { "" })
+  }
+
+  fn view_size(&self) -> Coord { self.v_size }
+
+  fn set_view_size(&mut self, v_size: Coord) {
+    self.v_size = Coord(cmp::max(1, v_size.col()), cmp::max(1, v_size.row()));
+    let at = self.at;
+    self.move_to(at);
+  }
+}
+
+impl Navigable for HexEdit {
+  fn cursor_up(&mut self) {
+    if self.at >= ROW_BYTES {
+      let at = self.at - ROW_BYTES;
+      self.move_to(at);
+    }
+  }
+
+  fn cursor_down(&mut self) {
+    if self.at / ROW_BYTES + 1 < self.rows() {
+      let at = self.at + ROW_BYTES;
+      self.move_to(at);
+    }
+  }
+
+  // The cursor steps over each hex digit, or each byte in the ASCII column.
+  fn cursor_left(&mut self) {
+    if self.low && !self.ascii {
+      self.low = false;
+    } else if self.at > 0 {
+      let at = self.at - 1;
+      self.move_to(at);
+      self.low = !self.ascii;
+    }
+  }
+
+  fn cursor_right(&mut self) {
+    if !self.low && !self.ascii && !self.bytes.is_empty() {
+      self.low = true;
+    } else if self.at < self.last() {
+      let at = self.at + 1;
+      self.move_to(at);
+      self.low = false;
+    }
+  }
+
+  fn page_up(&mut self) {
+    let rows = self.v_size.row();
+    let at = self.at.saturating_sub(rows * ROW_BYTES);
+    self.top = self.top.saturating_sub(rows);
+    self.move_to(at);
+  }
+
+  fn page_down(&mut self) {
+    let rows = self.v_size.row();
+    let at = self.at + rows * ROW_BYTES;
+    self.top = cmp::min(self.top + rows, self.rows() - 1);
+    self.move_to(at);
+  }
+
+  fn home(&mut self) {
+    let at = self.at - self.at % ROW_BYTES;
+    self.move_to(at);
+    self.low = false;
+  }
+
+  fn end(&mut self) {
+    let at = self.at - self.at % ROW_BYTES + ROW_BYTES - 1;
+    self.move_to(at);
+    self.low = false;
+  }
+
+  // Lines are rows of 16 bytes.
+  fn goto_line(&mut self, line: usize) {
+    self.move_to(line * ROW_BYTES);
+    self.low = false;
+  }
+
+  // Clicking a hex digit or an ASCII char moves to it and types into its
+  // column.
+  fn cursor_to(&mut self, at: Coord) {
+    let first = (self.top + at.row()) * ROW_BYTES;
+    let x = at.col();
+    let (col, low, ascii) = if x >= ASCII_X {
+      (x - ASCII_X, false, true)
+    } else if x >= HEX_X {
+      ((x - HEX_X) / 3, (x - HEX_X) % 3 == 1, false)
+    } else {
+      return;
+    };
+    if col < ROW_BYTES && first + col < self.bytes.len() {
+      self.move_to(first + col);
+      self.low = low;
+      self.ascii = ascii;
+    }
+  }
+
+  fn scroll_up(&mut self, rows: usize) {
+    self.top = self.top.saturating_sub(rows);
+    let bottom = self.top + self.v_size.row() - 1;
+    if self.at / ROW_BYTES > bottom {
+      let at = bottom * ROW_BYTES + self.at % ROW_BYTES;
+      self.move_to(at);
+    }
+  }
+
+  fn scroll_down(&mut self, rows: usize) {
+    let last = self.rows().saturating_sub(self.v_size.row());
+    self.top = cmp::min(self.top + rows, cmp::max(last, self.top));
+    let top = self.top * ROW_BYTES;
+    if self.at < top {
+      let at = top + self.at % ROW_BYTES;
+      self.move_to(at);
+    }
+  }
+
+  // The cursor's column counts hex digits, so it keeps which digit it is on.
+  fn view(&self) -> (Coord, Coord) {
+    let col = self.at % ROW_BYTES * 2 + self.low as usize;
+    (Coord(0, self.top), Coord(col, self.at / ROW_BYTES - self.top))
+  }
+
+  fn set_view(&mut self, view: (Coord, Coord)) {
+    let (offset, cursor) = view;
+    self.top = cmp::min(offset.row(), self.rows() - 1);
+    let row = self.top + cursor.row();
+    self.move_to(row * ROW_BYTES + cursor.col() / 2);
+    self.low = cursor.col() % 2 == 1 && !self.bytes.is_empty();
+  }
+}
+
+impl Editable for HexEdit {
+  // Hex digits overwrite the digit under the cursor, or in the ASCII column
+  // printable chars overwrite the byte. Tab switches between the columns and
+  // backspace moves back. The length of the file never changes.
+  fn insert(&mut self, ch: char) {
+    match ch {
+      '\t' => {
+        self.ascii = !self.ascii;
+        self.low = false;
+        return;
+      }
+      '\x08' => return self.cursor_left(),
+      _ => (),
+    }
+    if self.bytes.is_empty() {
+      return;
+    }
+    let byte = self.bytes[self.at];
+    if self.ascii {
+      if ' ' <= ch && ch <= '~' {
+        self.overwrite(ch as u8);
+        self.cursor_right();
+      }
+    } else if let Some(digit) = ch.to_digit(16) {
+      let digit = digit as u8;
+      let byte = if self.low {
+        byte & 0xf0 | digit
+      } else {
+        byte & 0x0f | digit << 4
+      };
+      self.overwrite(byte);
+      self.cursor_right();
+    }
+  }
+
+  // Lines can't be deleted without changing the length.
+  fn delete_line(&mut self) -> String { String::new() }
+
+  fn undo(&mut self) -> bool {
+    match self.undo.pop() {
+      Some(patch) => {
+        self.patch(Patch {
+          at: patch.at,
+          low: patch.low,
+          before: patch.after,
+          after: patch.before,
+        });
+        self.redo.push(patch);
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn redo(&mut self) -> bool {
+    match self.redo.pop() {
+      Some(patch) => {
+        self.patch(patch);
+        self.undo.push(patch);
+        true
+      }
+      None => false,
+    }
+  }
+}
+
+impl Searchable for HexEdit {
+  // Searches on from the byte after the cursor, then from the start.
+  fn find_next(&mut self, re: &Regex) -> bool {
+    let from = cmp::min(self.at + 1, self.bytes.len());
+    let next = find(&self.bytes[from..], re)
+      .map(|at| from + at)
+      .or_else(|| find(&self.bytes, re));
+    match next {
+      Some(at) => {
+        self.move_to(at);
+        self.low = false;
+        true
+      }
+      None => false,
+    }
+  }
+
+  fn set_search(&mut self, re: Option<Regex>) {
+    self.search = re;
+  }
+
+  // Replacing could change the length, so only finding is supported.
+  fn replace(&mut self, _: &Regex, _: &str) -> bool { false }
+  fn replace_all(&mut self, _: &Regex, _: &str) -> usize { 0 }
+  fn can_replace(&self) -> bool { false }
+}
@@ -0,0 +1,383 @@
+use regex::{Captures, Regex};
+use std::path::Path;
+use textbox::*;
+use {Highlighter, Span, State, Token};
+
+// The style each kind of token is drawn in.
+pub fn token_style(token: Token) -> Style {
+  match token {
+    Token::Comment => BLUE,
+    Token::String => GREEN,
+    Token::Keyword => MAGENTA,
+    Token::Number => CYAN,
+    Token::Type => YELLOW,
+    Token::Meta => RED,
+    Token::Heading => YELLOW | BOLD,
+    Token::Emphasis => DEFAULT | UNDERLINE,
+    Token::Code => GREEN,
+    Token::Link => BLUE | UNDERLINE,
+    Token::Column(n) => [DEFAULT, CYAN, YELLOW, GREEN, MAGENTA, BLUE][n % 6],
+  }
+}
+
+// Picks a highlighter by the extension of `path`.
+pub fn highlighter_for(path: &Path) -> Option<Box<Highlighter>> {
+  match path.extension().and_then(|ext| ext.to_str()) {
+    Some("rs") => Some(Box::new(Rust::new())),
+    Some("toml") => Some(Box::new(Toml::new())),
+    Some("md") | Some("markdown") => Some(Box::new(Markdown::new())),
+    Some("csv") => Some(Box::new(Csv)),
+    _ => None,
+  }
+}
+
+fn push(spans: &mut Vec<Span>, start: usize, end: usize, token: Token) {
+  if start < end {
+    spans.push(Span {
+      start: start,
+      end: end,
+      token: token,
+    });
+  }
+}
+
+// Tokens that start and end on one line, each matched by a regular expression
+// without capture groups of its own. Where several match the leftmost wins,
+// then the first listed.
+struct Rules {
+  re: Regex,
+  tokens: Vec<Token>,
+}
+
+impl Rules {
+  fn new(rules: &[(&str, Token)]) -> Rules {
+    let groups: Vec<String> =
+      rules.iter().map(|&(re, _)| format!("({})", re)).collect();
+    Rules {
+      re: Regex::new(&groups.join("|")).unwrap(),
+      tokens: rules.iter().map(|&(_, token)| token).collect(),
+    }
+  }
+
+  // Adds the spans of the tokens in `line[start..end]`.
+  fn spans(&self, line: &str, start: usize, end: usize, spans: &mut Vec<Span>) {
+    for caps in self.re.captures_iter(&line[start..end]) {
+      if let Some(i) = (1..caps.len()).find(|&i| caps.get(i).is_some()) {
+        let m = caps.get(i).unwrap();
+        push(spans, start + m.start(), start + m.end(), self.tokens[i - 1]);
+      }
+    }
+  }
+}
+
+// What a match of `Regions::starts` begins.
+enum Start {
+  // A token ending on the same line.
+  Token(Token),
+  // A comment running to the end of the line.
+  LineComment,
+  // A region, such as a block comment, that may run over several lines.
+  Region(State),
+}
+
+// A grammar whose strings and comments, which may hold anything and may run
+// over several lines, are found by hand, with `rules` for the code between.
+trait Regions {
+  fn starts(&self) -> &Regex;
+  fn rules(&self) -> &Rules;
+  fn start(&self, caps: &Captures) -> Start;
+  // Finds the end of the region `state` in `line`, from `at`. Returns where
+  // it ends and the state after, which is still a region if it didn't end.
+  fn close(&self, line: &str, at: usize, state: State) -> (usize, State);
+  fn token(&self, state: State) -> Token;
+}
+
+fn highlight_regions<R: Regions>(grammar: &R, line: &str, state: State)
+                                 -> (Vec<Span>, State) {
+  let mut spans = Vec::new();
+  let mut state = state;
+  let mut start = 0;
+  let mut at = 0;
+  loop {
+    if state != 0 {
+      let (end, next) = grammar.close(line, at, state);
+      push(&mut spans, start, end, grammar.token(state));
+      if next != 0 {
+        return (spans, next);
+      }
+      state = 0;
+      at = end;
+    }
+
+    let (open, open_end, what) = match grammar.starts().captures(&line[at..]) {
+      Some(caps) => {
+        let m = caps.get(0).unwrap();
+        (at + m.start(), at + m.end(), grammar.start(&caps))
+      }
+      None => {
+        grammar.rules().spans(line, at, line.len(), &mut spans);
+        return (spans, 0);
+      }
+    };
+    grammar.rules().spans(line, at, open, &mut spans);
+    match what {
+      Start::Token(token) => push(&mut spans, open, open_end, token),
+      Start::LineComment => {
+        push(&mut spans, open, line.len(), Token::Comment);
+        return (spans, 0);
+      }
+      Start::Region(region) => {
+        state = region;
+        start = open;
+      }
+    }
+    at = open_end;
+  }
+}
+
+// The end of a string whose closing quote is `quote` and whose escapes start
+// with a backslash.
+fn escaped_end(line: &str, at: usize, quote: char) -> Option<usize> {
+  let mut escaped = false;
+  for (i, ch) in line[at..].char_indices() {
+    if escaped {
+      escaped = false;
+    } else if ch == '\\' {
+      escaped = true;
+    } else if ch == quote {
+      return Some(at + i + 1);
+    }
+  }
+  None
+}
+
+// Rust's states are the depth of nested block comments, or one of these.
+const RUST_STRING: State = 0x1_0000;
+// Followed by the number of '#'s that close the raw string.
+const RUST_RAW_STRING: State = 0x2_0000;
+
+struct Rust {
+  starts: Regex,
+  rules: Rules,
+}
+
+impl Rust {
+  fn new() -> Rust {
+    Rust {
+      // Char literals are matched here so a quote in one isn't a string.
+      starts: Regex::new(r#"//|/\*|\bb?r(#*)"|(?:\bb)?"|'(?:\\.|[^\\'])'"#)
+        .unwrap(),
+      rules: Rules::new(&[(r#"#!?\[[^\]"]*\]?"#, Token::Meta),
+                          (r"\b[a-z_][a-zA-Z0-9_]*!", Token::Meta),
+                          (concat!(r"\b(?:as|break|const|continue|crate|",
+                                   r"dyn|else|enum|extern|false|fn|for|if|",
+                                   r"impl|in|let|loop|match|mod|move|mut|",
+                                   r"pub|ref|return|self|Self|static|",
+                                   r"struct|super|trait|true|type|unsafe|",
+                                   r"use|where|while)\b"),
+                           Token::Keyword),
+                          (concat!(r"\b(?:bool|char|str|f32|f64|",
+                                   r"[iu](?:8|16|32|64|128|size))\b"),
+                           Token::Type),
+                          (r"\b[A-Z][A-Za-z0-9_]*\b", Token::Type),
+                          (concat!(r"\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|0b[01_]+|",
+                                   r"\d[\d_]*(?:\.\d[\d_]*)?(?:[eE][+-]?\d+)?)",
+                                   r"(?:[iu](?:8|16|32|64|128|size)|f32|f64)?",
+                                   r"\b"),
+                           Token::Number)]),
+    }
+  }
+}
+
+impl Regions for Rust {
+  fn starts(&self) -> &Regex { &self.starts }
+  fn rules(&self) -> &Rules { &self.rules }
+
+  fn start(&self, caps: &Captures) -> Start {
+    let open = caps.get(0).unwrap().as_str();
+    if open == "//" {
+      Start::LineComment
+    } else if open == "/*" {
+      Start::Region(1)
+    } else if let Some(hashes) = caps.get(1) {
+      Start::Region(RUST_RAW_STRING + hashes.as_str().len() as State)
+    } else if open.ends_with('\'') {
+      Start::Token(Token::String)
+    } else {
+      Start::Region(RUST_STRING)
+    }
+  }
+
+  fn close(&self, line: &str, at: usize, state: State) -> (usize, State) {
+    if state >= RUST_RAW_STRING {
+      let hashes = (state - RUST_RAW_STRING) as usize;
+      let close = format!("\"{}", "#".repeat(hashes));
+      match line[at..].find(&close) {
+        Some(i) => (at + i + close.len(), 0),
+        None => (line.len(), state),
+      }
+    } else if state == RUST_STRING {
+      match escaped_end(line, at, '"') {
+        Some(end) => (end, 0),
+        None => (line.len(), state),
+      }
+    } else {
+      // Block comments nest.
+      let mut depth = state;
+      let mut at = at;
+      while let Some(i) = line[at..].find("/*").into_iter()
+        .chain(line[at..].find("*/"))
+        .min() {
+        let closes = line[at + i..].starts_with("*/");
+        at += i + 2;
+        if closes {
+          depth -= 1;
+          if depth == 0 {
+            return (at, 0);
+          }
+        } else if depth + 1 < RUST_STRING {
+          depth += 1;
+        }
+      }
+      (line.len(), depth)
+    }
+  }
+
+  fn token(&self, state: State) -> Token {
+    if state < RUST_STRING { Token::Comment } else { Token::String }
+  }
+}
+
+impl Highlighter for Rust {
+  fn highlight(&self, line: &str, state: State) -> (Vec<Span>, State) {
+    highlight_regions(self, line, state)
+  }
+}
+
+// TOML's multi-line basic and literal strings.
+const TOML_BASIC: State = 1;
+const TOML_LITERAL: State = 2;
+
+struct Toml {
+  starts: Regex,
+  rules: Rules,
+}
+
+impl Toml {
+  fn new() -> Toml {
+    Toml {
+      starts: Regex::new(r#"""""|'''|"(?:\\.|[^\\"])*"|'[^']*'|#"#).unwrap(),
+      rules: Rules::new(&[(r"^\s*\[\[?[^\]]*\]\]?", Token::Heading),
+                          (r"^\s*[A-Za-z0-9_-]+(?:\s*\.\s*[A-Za-z0-9_-]+)*\s*=",
+                           Token::Type),
+                          (r"\b(?:true|false)\b", Token::Keyword),
+                          (concat!(r"\d{4}-\d\d-\d\d(?:[T ]\d\d:\d\d:\d\d",
+                                   r"(?:\.\d+)?(?:Z|[+-]\d\d:\d\d)?)?"),
+                           Token::Number),
+                          (concat!(r"[+-]?\b(?:0x[0-9a-fA-F_]+|0o[0-7_]+|",
+                                   r"0b[01_]+|\d[\d_]*(?:\.\d[\d_]*)?",
+                                   r"(?:[eE][+-]?\d+)?|inf|nan)\b"),
+                           Token::Number)]),
+    }
+  }
+}
+
+impl Regions for Toml {
+  fn starts(&self) -> &Regex { &self.starts }
+  fn rules(&self) -> &Rules { &self.rules }
+
+  fn start(&self, caps: &Captures) -> Start {
+    match caps.get(0).unwrap().as_str() {
+      "#" => Start::LineComment,
+      "\"\"\"" => Start::Region(TOML_BASIC),
+      "'''" => Start::Region(TOML_LITERAL),
+      _ => Start::Token(Token::String),
+    }
+  }
+
+  fn close(&self, line: &str, at: usize, state: State) -> (usize, State) {
+    let close = if state == TOML_BASIC { "\"\"\"" } else { "'''" };
+    match line[at..].find(close) {
+      Some(i) => (at + i + close.len(), 0),
+      None => (line.len(), state),
+    }
+  }
+
+  fn token(&self, _: State) -> Token { Token::String }
+}
+
+impl Highlighter for Toml {
+  fn highlight(&self, line: &str, state: State) -> (Vec<Span>, State) {
+    highlight_regions(self, line, state)
+  }
+}
+
+// Inside a fenced code block.
+const MARKDOWN_FENCED: State = 1;
+
+struct Markdown {
+  fence: Regex,
+  rules: Rules,
+}
+
+impl Markdown {
+  fn new() -> Markdown {
+    Markdown {
+      fence: Regex::new(r"^\s{0,3}(?:```|~~~)").unwrap(),
+      rules: Rules::new(&[(r"^#{1,6}(?:\s.*)?$", Token::Heading),
+                          (r"^\s{0,3}>.*", Token::Comment),
+                          (r"^\s*(?:[-*+]|\d+[.)])\s", Token::Keyword),
+                          (r"`[^`]+`", Token::Code),
+                          (r"!?\[[^\]]*\]\([^)]*\)", Token::Link),
+                          (concat!(r"\*\*[^*]+\*\*|__[^_]+__|",
+                                   r"\*[^*\s][^*]*\*|\b_[^_\s][^_]*_\b"),
+                           Token::Emphasis)]),
+    }
+  }
+}
+
+impl Highlighter for Markdown {
+  fn highlight(&self, line: &str, state: State) -> (Vec<Span>, State) {
+    let mut spans = Vec::new();
+    if self.fence.is_match(line) {
+      push(&mut spans, 0, line.len(), Token::Code);
+      (spans, state ^ MARKDOWN_FENCED)
+    } else if state == MARKDOWN_FENCED {
+      push(&mut spans, 0, line.len(), Token::Code);
+      (spans, state)
+    } else {
+      self.rules.spans(line, 0, line.len(), &mut spans);
+      (spans, state)
+    }
+  }
+}
+
+// Colors the fields of comma separated values by column. Quoted fields may
+// hold newlines, so the state is the column, shifted left one, with the low
+// bit set while in quotes.
+struct Csv;
+
+impl Highlighter for Csv {
+  fn highlight(&self, line: &str, state: State) -> (Vec<Span>, State) {
+    let mut spans = Vec::new();
+    let mut column = (state >> 1) as usize;
+    let mut quoted = state & 1 != 0;
+    let mut start = 0;
+    for (i, b) in line.bytes().enumerate() {
+      if b == b'"' {
+        // An escaped "" flips this twice.
+        quoted = !quoted;
+      } else if b == b',' && !quoted {
+        push(&mut spans, start, i, Token::Column(column));
+        column += 1;
+        start = i + 1;
+      }
+    }
+    push(&mut spans, start, line.len(), Token::Column(column));
+    if quoted {
+      (spans, (column as State) << 1 | 1)
+    } else {
+      (spans, 0)
+    }
+  }
+}
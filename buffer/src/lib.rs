@@ -0,0 +1,178 @@
+#[cfg(unix)]
+extern crate libc;
+extern crate regex;
+extern crate ropey;
+extern crate textbox;
+extern crate unicode_segmentation;
+
+use regex::Regex;
+use std::io;
+use std::path::{Path, PathBuf};
+use textbox::*;
+
+mod atomic;
+mod buffer_list;
+mod command_bar;
+mod editor;
+mod encoding;
+mod file_edit;
+mod hex_edit;
+mod highlight;
+mod text;
+mod window;
+
+pub use buffer_list::BufferList;
+pub use command_bar::{CommandBar, Mode, Request};
+pub use editor::run;
+pub use encoding::Encoding;
+pub use file_edit::FileEdit;
+pub use hex_edit::HexEdit;
+pub use highlight::{highlighter_for, token_style};
+pub use window::{Split, Windows};
+
+/// What ends each line of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LineEnding {
+  Lf,
+  CrLf,
+}
+
+impl LineEnding {
+  pub fn as_str(&self) -> &'static str {
+    match *self {
+      LineEnding::Lf => "\n",
+      LineEnding::CrLf => "\r\n",
+    }
+  }
+
+  pub fn name(&self) -> &'static str {
+    match *self {
+      LineEnding::Lf => "LF",
+      LineEnding::CrLf => "CRLF",
+    }
+  }
+}
+
+pub trait Buffer {
+  fn name(&self) -> &str;
+  /// Paints the buffer at `at` in a view of `v_size`, scrolled and with the
+  /// cursor as `view` from `Navigable::view` has them. Panes showing the same
+  /// buffer pass their own, leaving the buffer's own view alone.
+  fn paint(&self,
+           tbox: &mut Textbox,
+           at: Coord,
+           v_size: Coord,
+           view: (Coord, Coord),
+           active: bool);
+  fn status(&self) -> String;
+  fn view_size(&self) -> Coord;
+  /// Resizes the view, scrolling as needed to keep the cursor in it.
+  fn set_view_size(&mut self, v_size: Coord);
+}
+
+pub trait Save {
+  fn save(&mut self) -> io::Result<usize>;
+  /// Saves to `path`, which is saved to from then on.
+  fn save_as(&mut self, path: PathBuf) -> io::Result<usize>;
+  /// Whether there are changes since the buffer was opened or last saved.
+  fn is_dirty(&self) -> bool;
+  /// The file saved to, if there is one.
+  fn path(&self) -> Option<&Path>;
+  /// The line ending written after each line, which is the one the file
+  /// was read with.
+  fn line_ending(&self) -> LineEnding;
+  /// Whether the file was read with lines ending both ways, so that saving
+  /// will end them all with `line_ending`.
+  fn mixed_line_endings(&self) -> bool;
+  /// Converts every line to end with `ending` from the next save on.
+  fn set_line_ending(&mut self, ending: LineEnding);
+}
+
+pub trait Navigable {
+  fn cursor_up(&mut self);
+  fn cursor_down(&mut self);
+  fn cursor_left(&mut self);
+  fn cursor_right(&mut self);
+
+  fn page_up(&mut self);
+  fn page_down(&mut self);
+  fn home(&mut self);
+  fn end(&mut self);
+
+  fn goto_line(&mut self, line: usize);
+
+  /// Moves the cursor to the text drawn at `at`, relative to the view.
+  fn cursor_to(&mut self, at: Coord);
+  /// Scrolls the view by `rows`, keeping the cursor in view.
+  fn scroll_up(&mut self, rows: usize);
+  fn scroll_down(&mut self, rows: usize);
+
+  /// The scroll offset and the cursor within the view, for `set_view`.
+  fn view(&self) -> (Coord, Coord);
+  /// Restores a view, moving the cursor back inside the text if it has since
+  /// shrunk.
+  fn set_view(&mut self, view: (Coord, Coord));
+}
+
+pub trait Editable {
+  fn insert(&mut self, ch: char);
+  fn delete_line(&mut self) -> String;
+
+  fn undo(&mut self) -> bool;
+  fn redo(&mut self) -> bool;
+}
+
+pub trait Searchable {
+  /// Moves the cursor to the next match of `re` after the cursor, wrapping
+  /// around at the end of the buffer. Returns false if nothing matched.
+  fn find_next(&mut self, re: &Regex) -> bool;
+  fn set_search(&mut self, re: Option<Regex>);
+
+  /// Replaces the match of `re` under the cursor with `with`, expanding `$1`
+  /// style capture groups, and moves to the next match. Returns false once no
+  /// matches are left.
+  fn replace(&mut self, re: &Regex, with: &str) -> bool;
+  /// Replaces every match of `re` in the buffer. Returns the number replaced.
+  fn replace_all(&mut self, re: &Regex, with: &str) -> usize;
+  /// Whether `replace` can change the buffer at all, which it can't if the
+  /// buffer is read-only or its length is fixed.
+  fn can_replace(&self) -> bool;
+}
+
+/// The kinds of text a highlighter picks out. `token_style` gives the style
+/// each is drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Token {
+  Comment,
+  String,
+  Keyword,
+  Number,
+  Type,
+  /// Attributes and macros.
+  Meta,
+  Heading,
+  Emphasis,
+  Code,
+  Link,
+  /// A field of a table, numbered from 0.
+  Column(usize),
+}
+
+/// The bytes `start..end` of a line are a `token`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub token: Token,
+}
+
+/// What a highlighter carries from the end of one line to the start of the
+/// next, such as being inside a block comment. The first line starts in 0.
+pub type State = u32;
+
+pub trait Highlighter {
+  /// Splits `line` into spans, in order and not overlapping, given the state
+  /// at the end of the line before it. Returns the spans and the state at the
+  /// end of `line`.
+  fn highlight(&self, line: &str, state: State) -> (Vec<Span>, State);
+}
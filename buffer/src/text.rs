@@ -0,0 +1,119 @@
+use ropey::{Rope, RopeSlice};
+use std::borrow::Cow;
+use textbox::{char_width, Coord};
+use unicode_segmentation::UnicodeSegmentation;
+
+// Storage for the text of a buffer. Rows are separated by '\n' and columns
+// are byte offsets within a row. Implementations should make edits and row
+// lookups cheap even for very large files.
+pub trait Text {
+  fn rows(&self) -> usize;
+  fn row(&self, row: usize) -> Cow<str>;
+
+  fn insert_at(&mut self, at: Coord, text: &str);
+  fn remove_at(&mut self, at: Coord, len: usize);
+}
+
+// Buffers measure columns in grapheme clusters, so that an accented letter or
+// an emoji built from several chars is a single column. These convert between
+// those columns and the byte offsets used by `Text` and `Regex`.
+
+// The number of columns in `line`.
+pub fn cols(line: &str) -> usize { line.graphemes(true).count() }
+
+// The byte offset of column `col`, or the end of `line` if `col` is past it.
+pub fn col_to_byte(line: &str, col: usize) -> usize {
+  line.grapheme_indices(true).nth(col).map_or(line.len(), |(i, _)| i)
+}
+
+// The column containing the byte at `byte`.
+pub fn byte_to_col(line: &str, byte: usize) -> usize {
+  line.grapheme_indices(true).take_while(|&(i, g)| i + g.len() <= byte).count()
+}
+
+// The text of column `col`, which is empty past the end of `line`.
+pub fn col_str(line: &str, col: usize) -> &str {
+  line.graphemes(true).nth(col).unwrap_or("")
+}
+
+// The number of screen cells taken by the column `g` when drawn at screen
+// column `x`. A tab reaches the next multiple of `tab_width`. Otherwise cells
+// hold only one char so this is the width of the first char in the cluster.
+pub fn col_width(g: &str, x: usize, tab_width: usize) -> usize {
+  if g == "\t" {
+    tab_width - x % tab_width
+  } else {
+    g.chars().next().map_or(0, char_width)
+  }
+}
+
+// The screen column, from the start of `line`, where column `col` is drawn.
+pub fn col_to_x(line: &str, col: usize, tab_width: usize) -> usize {
+  line.graphemes(true).take(col).fold(0, |x, g| x + col_width(g, x, tab_width))
+}
+
+// The column drawn over screen column `x`, from the start of `line`, or the
+// end of `line` if `x` is past it.
+pub fn x_to_col(line: &str, x: usize, tab_width: usize) -> usize {
+  let mut end = 0;
+  for (col, g) in line.graphemes(true).enumerate() {
+    end += col_width(g, end, tab_width);
+    if x < end {
+      return col;
+    }
+  }
+  cols(line)
+}
+
+// The longest start of `s` that fits in `width` screen columns.
+pub fn clip(s: &str, width: usize) -> &str {
+  let mut x = 0;
+  for (i, ch) in s.char_indices() {
+    x += char_width(ch);
+    if x > width {
+      return &s[..i];
+    }
+  }
+  s
+}
+
+// The char drawn for `ch`. Control codes, which the terminal would act on
+// rather than draw, are shown by their Unicode pictures, or U+FFFD for those
+// without one.
+pub fn glyph(ch: char) -> char {
+  match ch as u32 {
+    c if c < 0x20 => ::std::char::from_u32(0x2400 + c).unwrap_or(ch),
+    0x7f => '\u{2421}',
+    c if 0x80 <= c && c < 0xa0 => '\u{fffd}',
+    _ => ch,
+  }
+}
+
+// Strips the trailing '\n' from a line of the rope.
+fn trim_newline(line: RopeSlice) -> RopeSlice {
+  let chars = line.len_chars();
+  if chars > 0 && line.char(chars - 1) == '\n' {
+    line.slice(..chars - 1)
+  } else {
+    line
+  }
+}
+
+impl Text for Rope {
+  fn rows(&self) -> usize { self.len_lines() }
+
+  fn row(&self, row: usize) -> Cow<str> { trim_newline(self.line(row)).into() }
+
+  fn insert_at(&mut self, at: Coord, text: &str) {
+    let byte = self.line_to_byte(at.row()) + at.col();
+    let ch = self.byte_to_char(byte);
+    self.insert(ch, text);
+  }
+
+  fn remove_at(&mut self, at: Coord, len: usize) {
+    let byte = self.line_to_byte(at.row()) + at.col();
+    let start = self.byte_to_char(byte);
+    let end = self.byte_to_char(byte + len);
+    self.remove(start..end);
+  }
+}
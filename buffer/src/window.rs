@@ -0,0 +1,341 @@
+use buffer_list::BufferList;
+use std::cmp;
+use std::mem;
+use textbox::*;
+use {Buffer, Navigable, Save};
+
+// The fewest rows a pane is given: one of text, its status line and prompt.
+const MIN_ROWS: usize = 3;
+const MIN_COLS: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Split {
+  // One pane above the other.
+  Horizontal,
+  // Side by side, with a border between.
+  Vertical,
+}
+
+// The screen is divided by a tree of splits with a pane at each leaf.
+enum Node {
+  Pane(usize),
+  // The split, the rows or columns given to the first node, and the nodes.
+  Split(Split, usize, Box<Node>, Box<Node>),
+}
+
+// The size of the first node of a split, kept within what `total` has room
+// for once `gap` cells are left for a border.
+fn clamp_first(first: usize, total: usize, min: usize, gap: usize) -> usize {
+  let most = total.saturating_sub(gap + min);
+  cmp::max(cmp::min(first, most), cmp::min(min, most))
+}
+
+impl Node {
+  fn is_pane(&self, pane: usize) -> bool {
+    match *self {
+      Node::Pane(p) => p == pane,
+      Node::Split(..) => false,
+    }
+  }
+
+  // The panes from top left to bottom right.
+  fn panes(&self, out: &mut Vec<usize>) {
+    match *self {
+      Node::Pane(p) => out.push(p),
+      Node::Split(_, _, ref a, ref b) => {
+        a.panes(out);
+        b.panes(out);
+      }
+    }
+  }
+
+  // Places every pane within `at` and `size`, collecting the borders of
+  // vertical splits as their top and height.
+  fn layout(&mut self,
+            at: Coord,
+            size: Coord,
+            panes: &mut [Pane],
+            borders: &mut Vec<(Coord, usize)>) {
+    match *self {
+      Node::Pane(p) => {
+        panes[p].at = at;
+        panes[p].size = size;
+      }
+      Node::Split(Split::Horizontal, ref mut first, ref mut a, ref mut b) => {
+        *first = clamp_first(*first, size.row(), MIN_ROWS, 0);
+        a.layout(at, Coord(size.col(), *first), panes, borders);
+        let rest = Coord(size.col(), size.row() - *first);
+        b.layout(at + first.to_row(), rest, panes, borders);
+      }
+      Node::Split(Split::Vertical, ref mut first, ref mut a, ref mut b) => {
+        *first = clamp_first(*first, size.col(), MIN_COLS, 1);
+        a.layout(at, Coord(*first, size.row()), panes, borders);
+        borders.push((at + first.to_col(), size.row()));
+        let rest = Coord(size.col().saturating_sub(*first + 1), size.row());
+        b.layout(at + (*first + 1).to_col(), rest, panes, borders);
+      }
+    }
+  }
+
+  // Replaces pane `pane` with a split of it and `new`.
+  fn split(&mut self, pane: usize, split: Split, first: usize, new: usize) {
+    match *self {
+      Node::Pane(p) if p == pane => {
+        *self = Node::Split(split,
+                            first,
+                            Box::new(Node::Pane(pane)),
+                            Box::new(Node::Pane(new)))
+      }
+      Node::Pane(_) => (),
+      Node::Split(_, _, ref mut a, ref mut b) => {
+        a.split(pane, split, first, new);
+        b.split(pane, split, first, new);
+      }
+    }
+  }
+
+  // Removes pane `pane`, putting the other node of its split in the split's
+  // place, and renumbers the panes after it.
+  fn remove(&mut self, pane: usize) {
+    let other = match *self {
+      Node::Pane(ref mut p) => {
+        if *p > pane {
+          *p -= 1;
+        }
+        None
+      }
+      Node::Split(_, _, ref mut a, ref mut b) => {
+        if a.is_pane(pane) {
+          Some(mem::replace(&mut **b, Node::Pane(0)))
+        } else if b.is_pane(pane) {
+          Some(mem::replace(&mut **a, Node::Pane(0)))
+        } else {
+          a.remove(pane);
+          b.remove(pane);
+          None
+        }
+      }
+    };
+    if let Some(mut other) = other {
+      other.remove(pane);
+      *self = other;
+    }
+  }
+
+  // Moves the border of the split holding pane `pane` so the pane grows by
+  // `delta` cells. Returns false if it isn't in this node.
+  fn grow(&mut self, pane: usize, delta: isize) -> bool {
+    match *self {
+      Node::Pane(_) => false,
+      Node::Split(_, ref mut first, ref mut a, ref mut b) => {
+        let delta = if a.is_pane(pane) {
+          delta
+        } else if b.is_pane(pane) {
+          -delta
+        } else {
+          return a.grow(pane, delta) || b.grow(pane, delta);
+        };
+        // The layout keeps the size within the room there is.
+        *first = cmp::max(0, *first as isize + delta) as usize;
+        true
+      }
+    }
+  }
+}
+
+// A view of one of the buffers, placed at `at`. The focused pane's buffer
+// and view are those current in the buffer list, and are copied here only
+// when the focus moves away.
+#[derive(Clone, Copy, Debug)]
+struct Pane {
+  buf: usize,
+  view: (Coord, Coord),
+  at: Coord,
+  size: Coord,
+}
+
+// The screen split into panes, each showing one of the open buffers with its
+// own scroll offset and cursor. Several panes may show the same buffer.
+pub struct Windows<B> {
+  bufs: BufferList<B>,
+  root: Node,
+  panes: Vec<Pane>,
+  borders: Vec<(Coord, usize)>,
+  focus: usize,
+  size: Coord,
+}
+
+impl<B: Buffer + Navigable + Save> Windows<B> {
+  pub fn new(size: Coord, bufs: BufferList<B>) -> Self {
+    let pane = Pane {
+      buf: bufs.current_index(),
+      view: (zero(), zero()),
+      at: zero(),
+      size: size,
+    };
+    let mut windows = Windows {
+      bufs: bufs,
+      root: Node::Pane(0),
+      panes: vec![pane],
+      borders: vec![],
+      focus: 0,
+      size: size,
+    };
+    windows.save_focus();
+    windows.relayout();
+    windows
+  }
+
+  // The buffer list's current buffer is the one in the focused pane.
+  pub fn bufs(&self) -> &BufferList<B> { &self.bufs }
+  pub fn bufs_mut(&mut self) -> &mut BufferList<B> { &mut self.bufs }
+
+  pub fn panes(&self) -> usize { self.panes.len() }
+
+  fn save_focus(&mut self) {
+    if !self.bufs.is_empty() {
+      let pane = &mut self.panes[self.focus];
+      pane.buf = self.bufs.current_index();
+      pane.view = self.bufs.view();
+    }
+  }
+
+  fn load_focus(&mut self) {
+    if !self.bufs.is_empty() {
+      let pane = self.panes[self.focus];
+      self.bufs.select(pane.buf);
+      self.bufs.set_view(pane.view);
+      self.bufs.set_view_size(pane.size);
+    }
+  }
+
+  // Places the panes and loads the focused one, whose view must already
+  // have been saved.
+  fn relayout(&mut self) {
+    self.borders.clear();
+    self.root.layout(zero(), self.size, &mut self.panes, &mut self.borders);
+    self.load_focus();
+  }
+
+  pub fn set_size(&mut self, size: Coord) {
+    self.save_focus();
+    self.size = size;
+    self.relayout();
+  }
+
+  // Splits the focused pane in two showing the same buffer, and focuses the
+  // second.
+  pub fn split(&mut self, split: Split) {
+    self.save_focus();
+    let pane = self.panes[self.focus];
+    let first = match split {
+      Split::Horizontal => pane.size.row() / 2,
+      Split::Vertical => pane.size.col().saturating_sub(1) / 2,
+    };
+    let new = self.panes.len();
+    self.panes.push(pane);
+    self.root.split(self.focus, split, first, new);
+    self.focus = new;
+    self.relayout();
+  }
+
+  // Closes the focused pane, giving its room to its neighbor. The last pane
+  // can't be closed.
+  pub fn close_pane(&mut self) -> bool {
+    if self.panes.len() == 1 {
+      return false;
+    }
+    let closed = self.focus;
+    self.root.remove(closed);
+    self.panes.remove(closed);
+    self.focus = cmp::min(closed, self.panes.len() - 1);
+    self.relayout();
+    true
+  }
+
+  pub fn focus_next(&mut self) {
+    self.save_focus();
+    let mut order = vec![];
+    self.root.panes(&mut order);
+    let i = order.iter().position(|&p| p == self.focus).unwrap_or(0);
+    self.focus = order[(i + 1) % order.len()];
+    self.load_focus();
+  }
+
+  // Focuses the pane drawn at `at`, returning `at` relative to the pane, or
+  // None over a border.
+  pub fn focus_at(&mut self, at: Coord) -> Option<Coord> {
+    let hit = self.panes.iter().position(|pane| {
+      let end = pane.at + pane.size;
+      pane.at.col() <= at.col() && at.col() < end.col() &&
+      pane.at.row() <= at.row() && at.row() < end.row()
+    });
+    hit.map(|i| {
+      if i != self.focus {
+        self.save_focus();
+        self.focus = i;
+        self.load_focus();
+      }
+      at - self.panes[i].at
+    })
+  }
+
+  // Grows the focused pane by `delta` rows or columns, or shrinks it if
+  // negative, by moving the border of the split it is in.
+  pub fn resize_pane(&mut self, delta: isize) {
+    self.save_focus();
+    self.root.grow(self.focus, delta);
+    self.relayout();
+  }
+
+  // Adds `buf` to the buffer list and shows it in the focused pane.
+  pub fn open(&mut self, buf: B) {
+    self.bufs.push(buf);
+    self.save_focus();
+    self.load_focus();
+  }
+
+  // Closes the current buffer. Panes showing it switch to the buffer the
+  // list switches to.
+  pub fn close_buffer(&mut self) -> Option<B> {
+    let closed = self.bufs.current_index();
+    let buf = self.bufs.close();
+    if buf.is_some() {
+      let current = self.bufs.current_index();
+      for pane in self.panes.iter_mut() {
+        if pane.buf == closed {
+          pane.buf = current;
+          pane.view = (zero(), zero());
+        } else if pane.buf > closed {
+          pane.buf -= 1;
+        }
+      }
+      self.save_focus();
+      self.load_focus();
+    }
+    buf
+  }
+
+  // Paints every pane, each with its own view and only the focused one
+  // active, and the borders between them.
+  pub fn paint(&mut self, tbox: &mut Textbox) {
+    if self.bufs.is_empty() {
+      return;
+    }
+    self.save_focus();
+    for (i, pane) in self.panes.iter().enumerate() {
+      if i != self.focus {
+        let buf = self.bufs.get(pane.buf);
+        buf.paint(tbox, pane.at, pane.size, pane.view, false);
+      }
+    }
+    let focused = self.panes[self.focus];
+    self.bufs.paint(tbox, focused.at, focused.size, focused.view, true);
+
+    for &(at, rows) in self.borders.iter() {
+      for row in 0..rows {
+        tbox.set_cell(at + row.to_row(), '│', DEFAULT, DEFAULT);
+      }
+    }
+  }
+}
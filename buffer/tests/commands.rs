@@ -0,0 +1,109 @@
+extern crate buffer;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use std::fs;
+use textbox::*;
+
+#[test]
+fn lists_buffers_and_switches_to_the_picked_one() {
+  let v_size = Coord(20, 3);
+  let one = FileEdit::from_file(v_size, "one.txt").unwrap();
+  let two = FileEdit::from_file(v_size, "two.txt").unwrap();
+  let mut bufs = BufferList::new(v_size, vec![one, two]);
+  bufs.insert('x');
+  bufs.show_list();
+  let tbox = paint(&bufs, v_size);
+  assert_eq!(tbox.row_text(0), "> one.txt*");
+  assert_eq!(tbox.row_text(1), "  two.txt");
+  assert_eq!(tbox.row_text(2), "  *buffers* 1/2");
+
+  bufs.cursor_down();
+  bufs.insert('\n');
+  assert!(!bufs.picking());
+  assert_eq!(bufs.name(), "two.txt");
+  assert!(!bufs.is_dirty());
+  bufs.close();
+  assert_eq!(bufs.name(), "one.txt");
+  assert_eq!(bufs.len(), 1);
+}
+
+#[test]
+fn completes_and_opens_paths_beside_the_buffer() {
+  let dir = TempPath::dir("open");
+  fs::create_dir_all(dir.path().join("alpine")).unwrap();
+  fs::File::create(dir.path().join("alpha.txt")).unwrap();
+  let path = dir.path().join("notes.txt");
+  assert!(FileEdit::from_file(Coord(30, 1), dir.to_str()).is_err());
+  let buf = FileEdit::from_file(Coord(30, 1), path.to_str().unwrap()).unwrap();
+  let mut cmd = CommandBar::new(Coord(30, 1), buf);
+
+  cmd.push_mode(Mode::Open);
+  for ch in "al\t".chars() {
+    cmd.insert(ch);
+  }
+  let tbox = paint(&cmd, Coord(30, 3));
+  assert_eq!(tbox.row_text(2), ": alp");
+  assert!(tbox.row_text(1).contains("alpha.txt alpine/"));
+
+  for ch in "h\t\n".chars() {
+    cmd.insert(ch);
+  }
+  let alpha = dir.path().join("alpha.txt");
+  assert_eq!(cmd.take_request(), Some(Request::Open(alpha)));
+  assert_eq!(cmd.take_request(), None);
+}
+
+#[test]
+fn asks_before_closing_or_quitting_with_unsaved_changes() {
+  let mut cmd = CommandBar::new(Coord(20, 1), file_edit(Coord(20, 1), "x"));
+  cmd.confirm_close();
+  assert!(cmd.status().contains("*save before closing? s/d/c*"));
+  cmd.insert('c');
+  assert_eq!(cmd.take_request(), None);
+  cmd.confirm_close();
+  cmd.insert('d');
+  assert_eq!(cmd.take_request(), Some(Request::Close));
+
+  let dirty = vec!["a.txt".to_string(), "b.txt".to_string()];
+  cmd.confirm_quit(&dirty);
+  assert!(cmd.status().ends_with("2 unsaved: a.txt, b.txt"));
+  cmd.insert('s');
+  assert_eq!(cmd.take_request(), Some(Request::SaveAllAndQuit));
+  cmd.confirm_quit(&[]);
+  assert_eq!(cmd.take_request(), Some(Request::Quit));
+}
+
+#[test]
+fn closes_an_unnamed_buffer_once_it_is_saved_as_a_path() {
+  let path = TempPath::new("close-save-as.txt");
+  let mut cmd = CommandBar::new(Coord(60, 1), FileEdit::new(Coord(60, 1)));
+  cmd.insert('x');
+  cmd.confirm_close();
+  cmd.insert('s');
+  assert!(cmd.status().contains("*save as*"));
+  assert_eq!(cmd.take_request(), None);
+  for ch in path.to_str().chars() {
+    cmd.insert(ch);
+  }
+  cmd.insert('\n');
+  assert_eq!(cmd.take_request(), Some(Request::Close));
+  assert_eq!(path.read(), "x\n");
+
+  // Backing out of naming it keeps it open.
+  let mut cmd = CommandBar::new(Coord(60, 1), FileEdit::new(Coord(60, 1)));
+  cmd.insert('x');
+  cmd.confirm_close();
+  cmd.insert('s');
+  cmd.pop_mode();
+  cmd.push_mode(Mode::SaveAs);
+  for ch in path.to_str().chars() {
+    cmd.insert(ch);
+  }
+  cmd.insert('\n');
+  cmd.insert('y');
+  assert_eq!(cmd.take_request(), None);
+}
@@ -0,0 +1,60 @@
+// Helpers shared by the test files, each of which only uses some of them.
+#![allow(dead_code)]
+
+use buffer::*;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use textbox::*;
+
+// A path in the temp dir for one test, which is removed along with its
+// backup when dropped so that a failed assert leaves nothing behind.
+pub struct TempPath(PathBuf);
+
+impl TempPath {
+  pub fn new(name: &str) -> Self {
+    let name = format!("buffer-test-{}-{}", process::id(), name);
+    TempPath(env::temp_dir().join(name))
+  }
+
+  // A directory rather than a file.
+  pub fn dir(name: &str) -> Self {
+    let dir = TempPath::new(name);
+    fs::create_dir_all(dir.path()).unwrap();
+    dir
+  }
+
+  pub fn path(&self) -> &Path { &self.0 }
+  pub fn to_str(&self) -> &str { self.0.to_str().unwrap() }
+  pub fn read(&self) -> String { fs::read_to_string(&self.0).unwrap() }
+}
+
+impl Drop for TempPath {
+  fn drop(&mut self) {
+    if self.0.is_dir() {
+      let _ = fs::remove_dir_all(&self.0);
+    } else {
+      let _ = fs::remove_file(&self.0);
+      let _ = fs::remove_file(format!("{}~", self.0.display()));
+    }
+  }
+}
+
+// A buffer for a file that doesn't exist, holding `text`.
+pub fn file_edit(v_size: Coord, text: &str) -> FileEdit {
+  let path = env::temp_dir().join("buffer-test-does-not-exist.txt");
+  let mut buf = FileEdit::from_file(v_size, path.to_str().unwrap()).unwrap();
+  for ch in text.chars() {
+    buf.insert(ch);
+  }
+  buf
+}
+
+// Paints `buf` in its own view.
+pub fn paint<B: Buffer + Navigable>(buf: &B, size: Coord) -> MemoryTextbox {
+  let mut tbox = MemoryTextbox::new(size);
+  buf.paint(&mut tbox, zero(), buf.view_size(), buf.view(), true);
+  tbox.present();
+  tbox
+}
@@ -0,0 +1,72 @@
+extern crate buffer;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use std::fs;
+use std::io;
+use textbox::*;
+
+fn ctrl(key: char) -> Event { Event::Key('\0', CTRL, Key::Char(key)) }
+
+// Runs the editor on `files` until the scripted events in `tbox` quit it.
+fn run_script(tbox: &mut MemoryTextbox, files: &[&str]) {
+  run(tbox, files, |v_size, file| -> io::Result<FileEdit> {
+    match file {
+      Some(file) => FileEdit::from_file(v_size, file),
+      None => Ok(FileEdit::new(v_size)),
+    }
+  });
+  assert_eq!(tbox.pending(), 0);
+}
+
+#[test]
+fn types_saves_and_quits() {
+  let path = TempPath::new("editor.txt");
+  fs::write(path.path(), "world\n").unwrap();
+  let mut tbox = MemoryTextbox::new(Coord(30, 4));
+  tbox.push_keys("hello ");
+  tbox.push_event(ctrl('S'));
+  tbox.push_event(ctrl('Q'));
+  run_script(&mut tbox, &[path.to_str()]);
+  assert_eq!(path.read(), "hello world\n");
+}
+
+#[test]
+fn saves_everything_when_asked_on_quitting() {
+  let one = TempPath::new("editor-one.txt");
+  let two = TempPath::new("editor-two.txt");
+  let mut tbox = MemoryTextbox::new(Coord(30, 4));
+  tbox.push_keys("1");
+  tbox.push_event(ctrl('N'));
+  tbox.push_keys("2");
+  tbox.push_event(ctrl('Q'));
+  tbox.push_keys("s");
+  run_script(&mut tbox, &[one.to_str(), two.to_str()]);
+  assert_eq!(one.read(), "1\n");
+  assert_eq!(two.read(), "2\n");
+}
+
+#[test]
+fn reports_files_that_cannot_be_read() {
+  let dir = TempPath::dir("editor-dir");
+  let mut tbox = MemoryTextbox::new(Coord(60, 4));
+  tbox.push_event(ctrl('Q'));
+  run_script(&mut tbox, &[dir.to_str()]);
+  let message = format!("{}: ", dir.to_str());
+  assert!(tbox.row_text(2).contains(&message));
+}
+
+#[test]
+fn answers_the_quit_prompt_from_the_buffer_list() {
+  let path = TempPath::new("editor-list.txt");
+  let mut tbox = MemoryTextbox::new(Coord(30, 4));
+  tbox.push_keys("x");
+  tbox.push_event(ctrl('B'));
+  tbox.push_event(ctrl('Q'));
+  tbox.push_keys("d");
+  run_script(&mut tbox, &[path.to_str()]);
+  assert!(!path.path().exists());
+}
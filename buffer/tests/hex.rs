@@ -0,0 +1,125 @@
+extern crate buffer;
+extern crate regex;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use regex::Regex;
+use std::fs;
+use textbox::*;
+
+#[test]
+fn patches_bytes_in_hex_and_ascii_and_saves_them() {
+  let path = TempPath::new("hex.bin");
+  let mut bytes: Vec<u8> = (0..20).collect();
+  bytes[17] = b'A';
+  fs::write(path.path(), &bytes).unwrap();
+  let buf = HexEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  let mut cmd = CommandBar::new(Coord(80, 1), buf);
+  let tbox = paint(&cmd, Coord(80, 4));
+  assert_eq!(tbox.row_text(0),
+             "00000000  00 01 02 03 04 05 06 07 08 09 0a 0b 0c 0d 0e 0f  \
+              ................");
+  assert_eq!(tbox.row_text(1), format!("{:59}.A..", "00000010  10 41 12 13"));
+
+  cmd.cursor_right();
+  cmd.insert('f');
+  assert_eq!(paint(&cmd, Coord(80, 4)).cursor(), Some(Coord(13, 0)));
+  cmd.insert('e');
+  cmd.insert('z');
+  assert!(cmd.undo());
+  cmd.insert('e');
+  cmd.cursor_to(Coord(60, 1));
+  cmd.insert('\t');
+  cmd.insert('\t');
+  cmd.insert('!');
+  assert!(cmd.is_dirty());
+  assert_eq!(cmd.save().unwrap(), 20);
+  bytes[0] = 0x0f;
+  bytes[1] = 0xe1;
+  bytes[17] = b'!';
+  assert_eq!(fs::read(path.path()).unwrap(), bytes);
+
+  cmd.home();
+  cmd.push_mode(Mode::Find);
+  for ch in "\\x13\n".chars() {
+    cmd.insert(ch);
+  }
+  let tbox = paint(&cmd, Coord(80, 4));
+  assert!(tbox.snapshot().contains("1:19-21 CYAN | REVERSE on DEFAULT\n\
+                                    1:62-63 CYAN | REVERSE on DEFAULT\n"));
+  cmd.pop_mode();
+  assert_eq!(paint(&cmd, Coord(80, 4)).cursor(), Some(Coord(62, 1)));
+}
+
+#[test]
+fn finds_matches_again_as_bytes_are_patched() {
+  let path = TempPath::new("hex-matches.bin");
+  let mut bytes: Vec<u8> = (0..20).collect();
+  bytes[17] = b'!';
+  fs::write(path.path(), &bytes).unwrap();
+  let mut buf = HexEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  buf.set_search(Some(Regex::new("!").unwrap()));
+  buf.insert('2');
+  buf.insert('1');
+  let tbox = paint(&buf, Coord(80, 2));
+  assert!(tbox.snapshot().ends_with("0:10-12 YELLOW | REVERSE on DEFAULT\n\
+                                     0:59-60 YELLOW | REVERSE on DEFAULT\n\
+                                     1:13-15 YELLOW | REVERSE on DEFAULT\n\
+                                     1:60-61 YELLOW | REVERSE on DEFAULT\n"));
+}
+
+#[test]
+fn is_clean_after_undoing_back_to_the_saved_bytes() {
+  let path = TempPath::new("hex-undo.bin");
+  fs::write(path.path(), &[0u8, 1]).unwrap();
+  let mut buf = HexEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  buf.insert('a');
+  assert_eq!(buf.save().unwrap(), 2);
+  buf.insert('b');
+  assert!(buf.is_dirty());
+  assert!(buf.undo());
+  assert!(!buf.is_dirty());
+  assert!(buf.undo());
+  assert!(buf.is_dirty());
+  assert!(buf.redo());
+  assert!(!buf.is_dirty());
+
+  // Patching over an undone save leaves no way back to it.
+  assert!(buf.undo());
+  buf.insert('c');
+  assert!(buf.is_dirty());
+  assert!(buf.undo());
+  assert!(buf.is_dirty());
+}
+
+#[test]
+fn refuses_to_replace_bytes() {
+  let path = TempPath::new("hex-replace.bin");
+  fs::write(path.path(), b"abc").unwrap();
+  let buf = HexEdit::from_file(Coord(80, 1), path.to_str()).unwrap();
+  let mut cmd = CommandBar::new(Coord(80, 1), buf);
+  cmd.start_replace();
+  let tbox = paint(&cmd, Coord(80, 3));
+  assert!(tbox.row_text(1).contains("*edit* can't replace in this buffer"));
+}
+
+#[test]
+fn finds_bytes_out_of_view_and_wraps_around() {
+  let path = TempPath::new("hex-find.bin");
+  let mut bytes = vec![0u8; 100];
+  bytes[5] = b'!';
+  bytes[90] = b'!';
+  fs::write(path.path(), &bytes).unwrap();
+  let mut buf = HexEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  let re = Regex::new("!").unwrap();
+  assert!(buf.find_next(&re));
+  assert_eq!(buf.view(), (Coord(0, 0), Coord(10, 0)));
+  assert!(buf.find_next(&re));
+  assert_eq!(buf.view(), (Coord(0, 4), Coord(20, 1)));
+  assert!(buf.find_next(&re));
+  assert_eq!(buf.view(), (Coord(0, 0), Coord(10, 0)));
+  assert!(!buf.find_next(&Regex::new("\\x01").unwrap()));
+}
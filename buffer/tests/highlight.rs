@@ -0,0 +1,57 @@
+extern crate buffer;
+
+use buffer::*;
+use std::path::Path;
+
+// Highlights `lines` in order with the highlighter for `path`, giving the
+// text and token of each span on each line.
+fn tokens(path: &str, lines: &[&str]) -> Vec<Vec<(String, Token)>> {
+  let highlighter = highlighter_for(Path::new(path)).unwrap();
+  let mut state = 0;
+  let mut out = vec![];
+  for line in lines {
+    let (spans, next) = highlighter.highlight(line, state);
+    out.push(spans.iter()
+      .map(|span| (line[span.start..span.end].to_string(), span.token))
+      .collect());
+    state = next;
+  }
+  out
+}
+
+fn token(text: &str, token: Token) -> (String, Token) {
+  (text.to_string(), token)
+}
+
+#[test]
+fn picks_by_extension() {
+  assert!(highlighter_for(Path::new("src/main.rs")).is_some());
+  assert!(highlighter_for(Path::new("README.md")).is_some());
+  assert!(highlighter_for(Path::new("README.rst")).is_none());
+  assert!(highlighter_for(Path::new("Makefile")).is_none());
+}
+
+#[test]
+fn carries_rust_comments_and_raw_strings_across_lines() {
+  let lines = tokens("a.rs",
+                     &["let s = r#\"a \"quote\"",
+                       "\"#; /* a /* b */ c */ 1u8"]);
+  assert_eq!(lines[0],
+             vec![token("let", Token::Keyword),
+                  token("r#\"a \"quote\"", Token::String)]);
+  assert_eq!(lines[1],
+             vec![token("\"#", Token::String),
+                  token("/* a /* b */ c */", Token::Comment),
+                  token("1u8", Token::Number)]);
+}
+
+#[test]
+fn colors_csv_columns_through_quoted_newlines() {
+  let lines = tokens("a.csv", &["a,\"b", "c\",d"]);
+  assert_eq!(lines[0],
+             vec![token("a", Token::Column(0)),
+                  token("\"b", Token::Column(1))]);
+  assert_eq!(lines[1],
+             vec![token("c\"", Token::Column(1)),
+                  token("d", Token::Column(2))]);
+}
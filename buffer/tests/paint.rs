@@ -0,0 +1,101 @@
+extern crate buffer;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use textbox::*;
+
+#[test]
+fn paints_text_and_cursor() {
+  let buf = file_edit(Coord(8, 3), "one\n\ttwo");
+  let tbox = paint(&buf, Coord(8, 3));
+  assert_eq!(tbox.snapshot(), "one\n  two\n\n");
+  assert_eq!(tbox.cursor(), Some(Coord(5, 1)));
+}
+
+#[test]
+fn scrolls_long_lines_to_the_cursor() {
+  let buf = file_edit(Coord(4, 1), "abcdefgh");
+  let tbox = paint(&buf, Coord(4, 1));
+  assert_eq!(tbox.row_text(0), "fgh");
+  assert_eq!(tbox.cursor(), Some(Coord(3, 0)));
+}
+
+#[test]
+fn highlights_matches_in_find_mode() {
+  let buf = file_edit(Coord(20, 2), "cat hat\nbat");
+  let mut cmd = CommandBar::new(Coord(20, 1), buf);
+  cmd.goto_line(0);
+  cmd.home();
+  cmd.push_mode(Mode::Find);
+  for ch in "at\n".chars() {
+    cmd.insert(ch);
+  }
+
+  let tbox = paint(&cmd, Coord(20, 4));
+  assert_eq!(tbox.row_text(0), "cat hat");
+  assert_eq!(tbox.row_text(1), "bat");
+  assert!(tbox.row_text(2).contains("*find*"));
+  assert_eq!(tbox.row_text(3), ": at");
+  assert!(tbox.snapshot().ends_with("0:1-3 CYAN | REVERSE on DEFAULT\n\
+                                     0:5-7 YELLOW | REVERSE on DEFAULT\n\
+                                     1:1-3 YELLOW | REVERSE on DEFAULT\n\
+                                     2:0-20 DEFAULT on DEFAULT | REVERSE\n"));
+  assert_eq!(tbox.cursor(), Some(Coord(4, 3)));
+}
+
+#[test]
+fn splits_panes_with_their_own_views_of_a_buffer() {
+  let size = Coord(21, 4);
+  let buf = file_edit(Coord(21, 2), "one\ntwo\nthree");
+  let bufs = BufferList::new(size, vec![CommandBar::new(Coord(21, 1), buf)]);
+  let mut win = Windows::new(size, bufs);
+  win.split(Split::Vertical);
+  win.bufs_mut().goto_line(0);
+  win.bufs_mut().home();
+
+  let mut tbox = MemoryTextbox::new(size);
+  win.paint(&mut tbox);
+  tbox.present();
+  assert_eq!(tbox.row_text(0), "two       │one");
+  assert_eq!(tbox.row_text(1), "three     │two");
+  assert_eq!(tbox.cursor(), Some(Coord(11, 0)));
+
+  assert_eq!(win.focus_at(Coord(2, 1)), Some(Coord(2, 1)));
+  assert!(win.close_pane());
+  assert_eq!(win.panes(), 1);
+  let mut tbox = MemoryTextbox::new(size);
+  win.paint(&mut tbox);
+  tbox.present();
+  assert_eq!(tbox.row_text(0), "one");
+  assert_eq!(tbox.cursor(), Some(Coord(0, 0)));
+}
+
+#[test]
+fn paints_a_pane_whose_lines_were_cut_in_another() {
+  let size = Coord(21, 4);
+  let buf = file_edit(Coord(21, 2), "one\ntwo\nthree");
+  let bufs = BufferList::new(size, vec![CommandBar::new(Coord(21, 1), buf)]);
+  let mut win = Windows::new(size, bufs);
+  win.split(Split::Vertical);
+  win.focus_next();
+  win.bufs_mut().goto_line(1);
+  win.bufs_mut().delete_line();
+  win.bufs_mut().delete_line();
+  let view = win.bufs().view();
+
+  let mut tbox = MemoryTextbox::new(size);
+  win.paint(&mut tbox);
+  tbox.present();
+  assert_eq!(tbox.row_text(0), "one       │");
+  assert_eq!(win.bufs().view(), view);
+
+  win.focus_next();
+  let mut tbox = MemoryTextbox::new(size);
+  win.paint(&mut tbox);
+  tbox.present();
+  assert_eq!(tbox.row_text(0), "one       │one");
+  assert_eq!(tbox.cursor(), Some(Coord(14, 0)));
+}
@@ -0,0 +1,44 @@
+extern crate buffer;
+extern crate regex;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use regex::Regex;
+use textbox::*;
+
+#[test]
+fn replaces_matches_one_at_a_time() {
+  let mut buf = file_edit(Coord(20, 2), "cat hat\nbat");
+  buf.goto_line(0);
+  buf.home();
+  let re = Regex::new("(.)at").unwrap();
+  assert!(buf.replace(&re, "${1}og"));
+  assert!(buf.find_next(&re));
+  assert!(buf.replace(&re, "${1}og"));
+  assert_eq!(paint(&buf, Coord(20, 2)).snapshot(), "cog hat\nbog\n");
+  assert_eq!(buf.replace_all(&re, "x"), 1);
+  assert_eq!(paint(&buf, Coord(20, 2)).snapshot(), "cog x\nbog\n");
+}
+
+#[test]
+fn moves_past_empty_matches_as_they_are_replaced() {
+  let mut buf = file_edit(Coord(20, 2), "ab\ncd");
+  buf.goto_line(0);
+  buf.home();
+  let re = Regex::new("^").unwrap();
+  assert!(buf.replace(&re, ""));
+  assert_eq!(paint(&buf, Coord(20, 2)).cursor(), Some(Coord(0, 1)));
+
+  let re = Regex::new("x*").unwrap();
+  buf.goto_line(0);
+  buf.home();
+  for _ in 0..3 {
+    assert!(buf.replace(&re, "-"));
+  }
+  let tbox = paint(&buf, Coord(20, 2));
+  assert_eq!(tbox.snapshot(), "-a-b-\ncd\n");
+  assert_eq!(tbox.cursor(), Some(Coord(0, 1)));
+}
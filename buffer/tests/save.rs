@@ -0,0 +1,128 @@
+extern crate buffer;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use std::fs;
+use textbox::*;
+
+#[test]
+fn saves_scratch_buffers_as_a_path_confirming_overwrites() {
+  let path = TempPath::new("save-as.txt");
+  fs::File::create(path.path()).unwrap();
+  let mut cmd = CommandBar::new(Coord(60, 1), FileEdit::new(Coord(60, 1)));
+  cmd.insert('x');
+  assert_eq!(cmd.save().unwrap(), 0);
+  for ch in path.to_str().chars() {
+    cmd.insert(ch);
+  }
+  cmd.insert('\n');
+  assert!(cmd.status().contains("*overwrite? y/n*"));
+  assert!(cmd.is_dirty());
+
+  cmd.insert('y');
+  assert!(!cmd.is_dirty());
+  assert_eq!(cmd.path(), Some(path.path()));
+  assert_eq!(path.read(), "x\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn saves_through_a_temp_file_keeping_permissions_and_a_backup() {
+  use std::os::unix::fs::PermissionsExt;
+
+  let dir = TempPath::dir("save");
+  let path = dir.path().join("kept.txt");
+  fs::write(&path, "old\n").unwrap();
+  fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+  let mut buf =
+    FileEdit::from_file(Coord(20, 1), path.to_str().unwrap()).unwrap();
+  buf.set_backup(true);
+  buf.end();
+  buf.insert('!');
+  assert_eq!(buf.save().unwrap(), 5);
+  assert_eq!(fs::read_to_string(&path).unwrap(), "old!\n");
+  let backup = dir.path().join("kept.txt~");
+  assert_eq!(fs::read_to_string(backup).unwrap(), "old\n");
+  let mode = fs::metadata(&path).unwrap().permissions().mode();
+  assert_eq!(mode & 0o777, 0o640);
+  assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn keeps_line_endings_final_newline_and_bom_when_saving() {
+  let path = TempPath::new("endings.txt");
+  fs::write(path.path(), "\u{feff}one\r\ntwo\r\nthree").unwrap();
+  let mut buf = FileEdit::from_file(Coord(20, 3), path.to_str()).unwrap();
+  assert_eq!(buf.line_ending(), LineEnding::CrLf);
+  assert_eq!(paint(&buf, Coord(20, 3)).row_text(0), "one");
+  buf.goto_line(1);
+  buf.end();
+  buf.insert('!');
+  buf.save().unwrap();
+  assert_eq!(path.read(), "\u{feff}one\r\ntwo!\r\nthree");
+
+  buf.set_line_ending(LineEnding::Lf);
+  assert!(buf.is_dirty());
+  buf.save().unwrap();
+  assert_eq!(path.read(), "\u{feff}one\ntwo!\nthree");
+  assert!(!buf.mixed_line_endings());
+
+  fs::write(path.path(), "one\r\ntwo\nthree\r\n").unwrap();
+  let mut buf = FileEdit::from_file(Coord(20, 3), path.to_str()).unwrap();
+  assert!(buf.mixed_line_endings());
+  assert_eq!(buf.line_ending(), LineEnding::CrLf);
+  assert!(buf.status().ends_with(" - CRLF (mixed)"));
+  buf.insert('!');
+  buf.save().unwrap();
+  assert_eq!(path.read(), "!one\r\ntwo\r\nthree\r\n");
+  assert!(!buf.mixed_line_endings());
+  assert!(buf.status().ends_with(" - CRLF"));
+
+  // Converting them is as good as saving them all one way.
+  fs::write(path.path(), "one\r\ntwo\nthree\r\n").unwrap();
+  let mut buf = FileEdit::from_file(Coord(20, 3), path.to_str()).unwrap();
+  buf.set_line_ending(LineEnding::Lf);
+  assert!(!buf.mixed_line_endings());
+  assert!(!buf.status().contains("mixed"));
+}
+
+#[test]
+fn reads_and_saves_latin1_and_shows_binary_files_read_only() {
+  let path = TempPath::new("latin1.txt");
+  fs::write(path.path(), b"caf\xe9\x1b\n").unwrap();
+  let mut buf = FileEdit::from_file(Coord(20, 1), path.to_str()).unwrap();
+  assert_eq!(buf.encoding(), Encoding::Latin1);
+  assert_eq!(paint(&buf, Coord(20, 1)).row_text(0), "caf\u{e9}\u{241b}");
+  buf.end();
+  buf.insert('!');
+  buf.save().unwrap();
+  assert_eq!(fs::read(path.path()).unwrap(), b"caf\xe9\x1b!\n".to_vec());
+  buf.insert('\u{20ac}');
+  assert!(buf.save().is_err());
+  assert_eq!(fs::read(path.path()).unwrap(), b"caf\xe9\x1b!\n".to_vec());
+
+  fs::write(path.path(), b"\x7fELF\x00\x01").unwrap();
+  let mut buf = FileEdit::from_file(Coord(70, 1), path.to_str()).unwrap();
+  assert!(buf.is_read_only());
+  assert!(buf.status().ends_with("binary, read-only, open with --hex to edit"));
+  buf.insert('x');
+  assert!(!buf.is_dirty());
+  assert_eq!(paint(&buf, Coord(70, 1)).row_text(0),
+             "00000000  7f 45 4c 46 00 01                                \
+              .ELF..");
+
+  // The dump is laid out like `HexEdit`, which can edit the bytes.
+  let mut bytes: Vec<u8> = (0..20).collect();
+  bytes[17] = b'A';
+  fs::write(path.path(), &bytes).unwrap();
+  let buf = FileEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  let hex = HexEdit::from_file(Coord(80, 2), path.to_str()).unwrap();
+  let dump = paint(&buf, Coord(80, 2));
+  let edit = paint(&hex, Coord(80, 2));
+  assert_eq!(dump.row_text(0), edit.row_text(0));
+  assert_eq!(dump.row_text(1), edit.row_text(1));
+}
@@ -0,0 +1,56 @@
+extern crate buffer;
+extern crate textbox;
+
+mod common;
+
+use buffer::*;
+use common::*;
+use textbox::*;
+
+#[test]
+fn undoes_typing_as_one_step() {
+  let mut buf = file_edit(Coord(10, 1), "");
+  for ch in "word".chars() {
+    buf.insert(ch);
+  }
+  assert!(buf.undo());
+  assert_eq!(paint(&buf, Coord(10, 1)).row_text(0), "");
+  assert!(buf.redo());
+  assert_eq!(paint(&buf, Coord(10, 1)).row_text(0), "word");
+}
+
+#[test]
+fn undoes_a_combining_mark_typed_after_its_base() {
+  let path = TempPath::new("combining.txt");
+  let mut buf = file_edit(Coord(10, 1), "ez");
+  buf.home();
+  buf.cursor_right();
+  buf.insert('\u{301}');
+  buf.save_as(path.path().to_path_buf()).unwrap();
+  assert_eq!(path.read(), "e\u{301}z\n");
+  assert!(buf.undo());
+  buf.save().unwrap();
+  assert_eq!(path.read(), "ez\n");
+  assert!(buf.redo());
+  buf.save().unwrap();
+  assert_eq!(path.read(), "e\u{301}z\n");
+}
+
+#[test]
+fn is_clean_after_undoing_back_to_the_saved_text() {
+  let path = TempPath::new("clean.txt");
+  let mut buf = file_edit(Coord(10, 1), "one");
+  buf.save_as(path.path().to_path_buf()).unwrap();
+  buf.insert('s');
+  assert!(buf.is_dirty());
+  assert!(buf.undo());
+  assert!(!buf.is_dirty());
+  assert!(buf.undo());
+  assert!(buf.is_dirty());
+  assert!(buf.redo());
+  assert!(!buf.is_dirty());
+  buf.set_line_ending(LineEnding::CrLf);
+  assert!(buf.is_dirty());
+  buf.set_line_ending(LineEnding::Lf);
+  assert!(!buf.is_dirty());
+}
@@ -0,0 +1,528 @@
+// A Textbox that talks to the terminal directly: termios raw mode for input
+// and ANSI/VT escape sequences for output. Cells are drawn into a back buffer
+// and present writes only the cells that differ from what is on screen.
+
+use libc;
+use std::char;
+use std::env;
+use std::io::{self, Write};
+use std::mem;
+use std::ops::Drop;
+use std::os::raw::c_int;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub use types::*;
+use xterm::{to_csi_event, to_xterm_mods};
+
+// How long to wait for the rest of an escape sequence before taking what has
+// arrived as separate keys, such as a lone Esc.
+const ESC_DELAY_MS: c_int = 25;
+
+// Set by the SIGWINCH handler and picked up by pop_event.
+static RESIZED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_resize(_: c_int) { RESIZED.store(true, Ordering::SeqCst); }
+
+pub struct AnsiTerminal {
+  orig: libc::termios,
+  size: Coord,
+  front: Vec<Cell>,
+  back: Vec<Cell>,
+  cursor: Option<Coord>,
+  fg_clear: Style,
+  bg_clear: Style,
+  input_mode: InputMode,
+  output_mode: OutputMode,
+  input: Vec<u8>,
+}
+
+fn blank() -> Cell { Cell { ch: ' ', fg: DEFAULT, bg: DEFAULT } }
+
+fn term_size() -> Coord {
+  unsafe {
+    let mut ws: libc::winsize = mem::zeroed();
+    if libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut ws) == 0 &&
+       ws.ws_col > 0 && ws.ws_row > 0 {
+      Coord(ws.ws_col as usize, ws.ws_row as usize)
+    } else {
+      Coord(80, 24)
+    }
+  }
+}
+
+// The colors the terminal says it supports.
+fn detect_output_mode() -> OutputMode {
+  let colorterm = env::var("COLORTERM").unwrap_or(String::new());
+  let term = env::var("TERM").unwrap_or(String::new());
+  if colorterm == "truecolor" || colorterm == "24bit" {
+    OutputMode::TrueColor
+  } else if term.contains("256color") {
+    OutputMode::Colors256
+  } else {
+    OutputMode::Normal
+  }
+}
+
+// The SGR parameters that set `color`, where `base` is 30 for the foreground
+// and 40 for the background.
+fn color_sgr(color: Color, base: u8) -> String {
+  match color {
+    Color::Default => String::new(),
+    Color::Indexed(n) if n < 8 => format!(";{}", base + n),
+    Color::Indexed(n) => format!(";{};5;{}", base + 8, n),
+    Color::Rgb(r, g, b) => format!(";{};2;{};{};{}", base + 8, r, g, b),
+  }
+}
+
+// The SGR sequence that sets the colors and attributes of a cell. Like
+// termbox, attributes apply whether they are set on the fg or the bg.
+fn to_sgr(fg: Style, bg: Style, mode: OutputMode) -> String {
+  let attrs = fg | bg;
+  let mut sgr = "\x1b[0".to_string();
+  if attrs.intersects(BRIGHT | BOLD) {
+    sgr.push_str(";1");
+  }
+  if attrs.contains(UNDERLINE) {
+    sgr.push_str(";4");
+  }
+  if attrs.contains(REVERSE) {
+    sgr.push_str(";7");
+  }
+  sgr.push_str(&color_sgr(fg.color().downgrade(mode), 30));
+  sgr.push_str(&color_sgr(bg.color().downgrade(mode), 40));
+  sgr.push('m');
+  sgr
+}
+
+// Decodes a control byte as the Ctrl key that sends it.
+fn to_ctrl_event(byte: u8) -> Event {
+  let kc = match byte {
+    0x00 => Key::Char(' '),
+    0x1c => Key::Char('\\'),
+    0x1d => Key::Char(']'),
+    0x1e => Key::Char('6'),
+    0x1f => Key::Char('/'),
+    _ => Key::Char((b'A' + byte - 1) as char),
+  };
+  Event::Key('\0', CTRL, kc)
+}
+
+// Decodes an SGR mouse report, "\x1b[<b;x;yM" or "m" on release.
+fn to_mouse_event(params: &[u32], fin: char) -> Option<Event> {
+  if params.len() != 3 || params[1] == 0 || params[2] == 0 {
+    return None;
+  }
+  let b = params[0];
+  let button = if fin == 'm' {
+    Button::Release
+  } else if b & 64 != 0 {
+    if b & 1 == 0 { Button::WheelUp } else { Button::WheelDown }
+  } else {
+    match b & 3 {
+      0 => Button::Left,
+      1 => Button::Middle,
+      2 => Button::Right,
+      _ => Button::Release,
+    }
+  };
+  // The modifier bits are shift (4), alt (8) and ctrl (16).
+  let mods = to_xterm_mods(((b >> 2) & 7) + 1);
+  let at = Coord(params[1] as usize - 1, params[2] as usize - 1);
+  Some(Event::Mouse(button, mods, at))
+}
+
+// Decodes the sequence after "\x1b[" or "\x1bO". Returns the event, if the
+// sequence is one we know, and its length, or None if it is incomplete.
+fn decode_seq(input: &[u8]) -> Option<(Option<Event>, usize)> {
+  let end = match input.iter().position(|&b| b >= 0x40 && b <= 0x7e) {
+    Some(end) => end,
+    None => return None,
+  };
+  let fin = input[end] as char;
+  let (mouse, params) = match input.first() {
+    Some(&b'<') => (true, &input[1..end]),
+    _ => (false, &input[..end]),
+  };
+  let params: Vec<u32> = str::from_utf8(params)
+    .unwrap_or("")
+    .split(';')
+    .map(|param| param.parse().unwrap_or(1))
+    .collect();
+  let event = if mouse {
+    to_mouse_event(&params, fin)
+  } else {
+    to_csi_event(&params, fin)
+  };
+  Some((event, end + 1))
+}
+
+// Decodes the first key or mouse event in `input`. Returns the event, if any,
+// and the number of bytes it took, or None if `input` is empty or ends in the
+// middle of a sequence.
+fn decode(input: &[u8]) -> Option<(Option<Event>, usize)> {
+  let byte = match input.first() {
+    Some(&byte) => byte,
+    None => return None,
+  };
+  let event = match byte {
+    0x1b => {
+      return match input.get(1) {
+        None => None,
+        Some(&b'[') | Some(&b'O') => {
+          decode_seq(&input[2..]).map(|(event, len)| (event, len + 2))
+        }
+        // Esc followed by another key is that key with Alt held.
+        Some(_) => {
+          decode(&input[1..]).map(|(event, len)| {
+            let event = match event {
+              Some(Event::Key(ch, mods, kc)) => {
+                Some(Event::Key(ch, mods | ALT, kc))
+              }
+              event => event,
+            };
+            (event, len + 1)
+          })
+        }
+      };
+    }
+    // Raw mode leaves Enter as '\r', so '\n' is Ctrl-J like other letters.
+    b'\r' => Event::Key('\0', NO_MODS, Key::Enter),
+    b'\t' => Event::Key('\0', NO_MODS, Key::Tab),
+    0x7f => Event::Key('\0', NO_MODS, Key::Backspace),
+    byte if byte < 0x20 => to_ctrl_event(byte),
+    _ => {
+      let len = match byte {
+        byte if byte < 0x80 => 1,
+        byte if byte >= 0xf0 => 4,
+        byte if byte >= 0xe0 => 3,
+        _ => 2,
+      };
+      if input.len() < len {
+        return None;
+      }
+      return match str::from_utf8(&input[..len]) {
+        Ok(s) => {
+          let ch = s.chars().next().unwrap();
+          let kc = Key::Char(ch.to_ascii_uppercase());
+          Some((Some(Event::Key(ch, NO_MODS, kc)), len))
+        }
+        Err(_) => Some((None, 1)),
+      };
+    }
+  };
+  Some((Some(event), 1))
+}
+
+impl AnsiTerminal {
+  fn write(&self, out: &str) {
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(out.as_bytes());
+    let _ = stdout.flush();
+  }
+
+  // Waits up to `timeout` milliseconds, or forever if it is negative, for
+  // input and adds it to `input`. Returns false if nothing was read.
+  fn read_input(&mut self, timeout: c_int) -> bool {
+    let mut buf = [0u8; 256];
+    unsafe {
+      let mut fds = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+      };
+      // A resize interrupts the poll.
+      if libc::poll(&mut fds, 1, timeout) <= 0 {
+        return false;
+      }
+      let n = libc::read(libc::STDIN_FILENO,
+                         buf.as_mut_ptr() as *mut libc::c_void,
+                         buf.len());
+      if n <= 0 {
+        return false;
+      }
+      self.input.extend_from_slice(&buf[..n as usize]);
+    }
+    true
+  }
+
+  // Resizes the buffers to the terminal and clears it so everything is
+  // redrawn on the next present.
+  fn resize(&mut self) {
+    self.size = term_size();
+    let cells = self.size.0 * self.size.1;
+    self.front = vec![blank(); cells];
+    self.back = vec![blank(); cells];
+    self.clear();
+    self.write("\x1b[0m\x1b[2J");
+  }
+}
+
+impl Textbox for AnsiTerminal {
+  fn init() -> Result<Self> {
+    let orig = unsafe {
+      if libc::isatty(libc::STDIN_FILENO) == 0 {
+        return Err("stdin is not a terminal".to_string());
+      }
+      let mut orig: libc::termios = mem::zeroed();
+      if libc::tcgetattr(libc::STDIN_FILENO, &mut orig) != 0 {
+        return Err("tcgetattr failed".to_string());
+      }
+      let mut raw = orig;
+      libc::cfmakeraw(&mut raw);
+      if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) != 0 {
+        return Err("tcsetattr failed".to_string());
+      }
+
+      let mut action: libc::sigaction = mem::zeroed();
+      action.sa_sigaction =
+        on_resize as extern "C" fn(c_int) as libc::sighandler_t;
+      libc::sigemptyset(&mut action.sa_mask);
+      libc::sigaction(libc::SIGWINCH, &action, 0 as *mut libc::sigaction);
+      orig
+    };
+
+    let size = term_size();
+    let cells = size.0 * size.1;
+    let tbox = AnsiTerminal {
+      orig: orig,
+      size: size,
+      front: vec![blank(); cells],
+      back: vec![blank(); cells],
+      cursor: None,
+      fg_clear: DEFAULT,
+      bg_clear: DEFAULT,
+      input_mode: InputMode::Esc,
+      output_mode: detect_output_mode(),
+      input: Vec::new(),
+    };
+    // Switch to the alternate screen, clear it and hide the cursor.
+    tbox.write("\x1b[?1049h\x1b[0m\x1b[2J\x1b[?25l");
+    Ok(tbox)
+  }
+
+  fn size(&self) -> Coord { self.size }
+
+  fn set_clear_style(&mut self, fg: Style, bg: Style) {
+    self.fg_clear = fg;
+    self.bg_clear = bg;
+  }
+  fn clear(&mut self) {
+    for cell in self.back.iter_mut() {
+      cell.ch = ' ';
+      cell.fg = self.fg_clear;
+      cell.bg = self.bg_clear;
+    }
+  }
+
+  fn present(&mut self) {
+    let Coord(cols, rows) = self.size;
+    let mut out = String::new();
+    // Where the terminal's cursor is and the style it draws with.
+    let mut at = None;
+    let mut style = None;
+    for row in 0..rows {
+      let mut col = 0;
+      while col < cols {
+        let i = row * cols + col;
+        if self.back[i] == self.front[i] {
+          col += 1;
+          continue;
+        }
+        // The second half of a wide char is drawn by the char itself.
+        let col_at = if self.back[i].ch == WIDE_CONTINUATION && col > 0 &&
+                        char_width(self.back[i - 1].ch) == 2 {
+          col - 1
+        } else {
+          col
+        };
+        let cell = self.back[row * cols + col_at];
+        // Anything that wouldn't take exactly its cells is drawn as a space.
+        let width = char_width(cell.ch);
+        let ch = if cell.ch.is_control() || width == 0 ||
+                    col_at + width > cols {
+          ' '
+        } else {
+          cell.ch
+        };
+        if at != Some(Coord(col_at, row)) {
+          out.push_str(&format!("\x1b[{};{}H", row + 1, col_at + 1));
+        }
+        if style != Some((cell.fg, cell.bg)) {
+          out.push_str(&to_sgr(cell.fg, cell.bg, self.output_mode));
+          style = Some((cell.fg, cell.bg));
+        }
+        out.push(ch);
+        let width = char_width(ch);
+        for col in col_at..col_at + width {
+          self.front[row * cols + col] = self.back[row * cols + col];
+        }
+        col = col_at + width;
+        at = Some(Coord(col, row));
+      }
+    }
+    out.push_str("\x1b[0m");
+    match self.cursor {
+      Some(Coord(col, row)) => {
+        out.push_str(&format!("\x1b[{};{}H\x1b[?25h", row + 1, col + 1));
+      }
+      None => out.push_str("\x1b[?25l"),
+    }
+    self.write(&out);
+  }
+
+  fn set_cursor(&mut self, coord: Coord) {
+    if coord.0 < self.size.0 && coord.1 < self.size.1 {
+      self.cursor = Some(coord);
+    }
+  }
+  fn hide_cursor(&mut self) { self.cursor = None; }
+
+  fn put_cell(&mut self, coord: Coord, cell: Cell) {
+    if coord.0 < self.size.0 && coord.1 < self.size.1 {
+      self.back[coord.0 + coord.1 * self.size.0] = cell
+    }
+  }
+
+  fn set_input_mode(&mut self, mode: InputMode) -> InputMode {
+    match mode {
+      InputMode::Current => (),
+      // Click, drag and wheel reports in the SGR format, which has no limit
+      // on the coordinates.
+      InputMode::Mouse => self.write("\x1b[?1002h\x1b[?1006h"),
+      _ => self.write("\x1b[?1002l\x1b[?1006l"),
+    }
+    if mode != InputMode::Current {
+      // Alt is always decoded from the Esc that precedes the key.
+      self.input_mode = mode;
+    }
+    self.input_mode
+  }
+  fn set_output_mode(&mut self, mode: OutputMode) -> OutputMode {
+    if mode != OutputMode::Current && mode != self.output_mode {
+      self.output_mode = mode;
+      // Redraw everything in the new colors.
+      for cell in self.front.iter_mut() {
+        cell.ch = WIDE_CONTINUATION;
+      }
+    }
+    self.output_mode
+  }
+
+  fn pop_event(&mut self) -> Option<Event> {
+    loop {
+      if RESIZED.swap(false, Ordering::SeqCst) {
+        self.resize();
+        return Some(Event::Resize(self.size));
+      }
+      let (event, len) = match decode(&self.input) {
+        Some(decoded) => decoded,
+        None => {
+          let timeout = if self.input.is_empty() { -1 } else { ESC_DELAY_MS };
+          if self.read_input(timeout) || self.input.is_empty() {
+            continue;
+          }
+          // The rest of the sequence never came: the first byte is a key.
+          match self.input[0] {
+            0x1b => (Some(Event::Key('\0', NO_MODS, Key::Escape)), 1),
+            _ => (None, 1),
+          }
+        }
+      };
+      self.input.drain(..len);
+      if let Some(Event::Mouse(..)) = event {
+        if self.input_mode != InputMode::Mouse {
+          continue;
+        }
+      }
+      if event.is_some() {
+        return event;
+      }
+    }
+  }
+}
+
+impl Drop for AnsiTerminal {
+  fn drop(&mut self) {
+    if self.input_mode == InputMode::Mouse {
+      self.write("\x1b[?1002l\x1b[?1006l");
+    }
+    // Show the cursor and go back to the normal screen.
+    self.write("\x1b[0m\x1b[?25h\x1b[?1049l");
+    unsafe {
+      libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.orig);
+      libc::signal(libc::SIGWINCH, libc::SIG_DFL);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key(ch: char, mods: Mod, kc: Key) -> Option<Event> {
+    Some(Event::Key(ch, mods, kc))
+  }
+
+  fn mouse(button: Button, mods: Mod, at: Coord) -> Option<Event> {
+    Some(Event::Mouse(button, mods, at))
+  }
+
+  #[test]
+  fn decodes_keys() {
+    let cases: [(&[u8], Option<Event>); 12] =
+      [(b"\r", key('\0', NO_MODS, Key::Enter)),
+       (b"\n", key('\0', CTRL, Key::Char('J'))),
+       (b"\t", key('\0', NO_MODS, Key::Tab)),
+       (b"\x7f", key('\0', NO_MODS, Key::Backspace)),
+       (b"\x00", key('\0', CTRL, Key::Char(' '))),
+       (b"\x11", key('\0', CTRL, Key::Char('Q'))),
+       (b"q", key('q', NO_MODS, Key::Char('Q'))),
+       ("\u{e9}".as_bytes(), key('\u{e9}', NO_MODS, Key::Char('\u{e9}'))),
+       (b"\x1bx", key('x', ALT, Key::Char('X'))),
+       (b"\x1b\r", key('\0', ALT, Key::Enter)),
+       (b"\x1b[1;5A", key('\0', CTRL, Key::Up)),
+       (b"\x1bOR", key('\0', NO_MODS, Key::F(3)))];
+    for &(input, event) in cases.iter() {
+      assert_eq!(decode(input), Some((event, input.len())), "{:?}", input);
+    }
+  }
+
+  #[test]
+  fn decodes_sgr_mouse_reports() {
+    let cases: [(&[u8], Option<Event>); 5] =
+      [(b"\x1b[<0;3;2M", mouse(Button::Left, NO_MODS, Coord(2, 1))),
+       (b"\x1b[<2;1;1M", mouse(Button::Right, NO_MODS, Coord(0, 0))),
+       (b"\x1b[<16;5;9M", mouse(Button::Left, CTRL, Coord(4, 8))),
+       (b"\x1b[<0;3;2m", mouse(Button::Release, NO_MODS, Coord(2, 1))),
+       (b"\x1b[<65;1;1M", mouse(Button::WheelDown, NO_MODS, Coord(0, 0)))];
+    for &(input, event) in cases.iter() {
+      assert_eq!(decode(input), Some((event, input.len())), "{:?}", input);
+    }
+  }
+
+  #[test]
+  fn takes_one_event_at_a_time() {
+    assert_eq!(decode(b"ab"), Some((key('a', NO_MODS, Key::Char('A')), 1)));
+    assert_eq!(decode(b"\x1b[Ax"), Some((key('\0', NO_MODS, Key::Up), 3)));
+  }
+
+  // A lone Esc and the start of a sequence wait for more input, which
+  // pop_event gives up on after ESC_DELAY_MS.
+  #[test]
+  fn waits_for_the_rest_of_partial_input() {
+    let cases: [&[u8]; 6] =
+      [b"", b"\x1b", b"\x1b[", b"\x1b[1;5", b"\x1b[<0;3", b"\xc3"];
+    for input in cases.iter() {
+      assert_eq!(decode(input), None, "{:?}", input);
+    }
+  }
+
+  #[test]
+  fn skips_unknown_sequences_and_invalid_bytes() {
+    assert_eq!(decode(b"\x1b[99~"), Some((None, 5)));
+    assert_eq!(decode(b"\x1b[<0;0;1M"), Some((None, 9)));
+    assert_eq!(decode(b"\x80x"), Some((None, 1)));
+  }
+}
@@ -2,11 +2,15 @@
 extern crate bitflags;
 extern crate bit_set;
 extern crate num_traits;
+extern crate unicode_width;
+#[cfg(all(unix, feature = "ansi"))]
+extern crate libc;
 
 mod types {
   use std::ops::{Add, Sub};
   pub use num_traits::{zero, Zero};
   use std::result;
+  use unicode_width::UnicodeWidthChar;
 
   pub type Result<T> = result::Result<T, String>;
 
@@ -60,10 +64,16 @@ mod types {
   #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
   pub enum OutputMode {
     Current,
+    // The eight named colors.
     Normal,
+    // All 256 indexed colors.
     Colors256,
+    // Only the 6x6x6 color cube of the indexed colors.
     Colors216,
+    // Only the 24 step grayscale ramp of the indexed colors.
     Grayscale,
+    // 24-bit RGB.
+    TrueColor,
   }
 
   bitflags! {
@@ -114,13 +124,27 @@ mod types {
     Enter,
   }
 
+  #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+  pub enum Button {
+    Left,
+    Middle,
+    Right,
+    Release,
+    WheelUp,
+    WheelDown,
+  }
+
   #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
   pub enum Event {
     Key(char, Mod, Key),
+    // Mouse events are only reported in `InputMode::Mouse`.
+    Mouse(Button, Mod, Coord),
+    // The new size of the terminal.
+    Resize(Coord),
   }
 
   bitflags! {
-     pub struct Style: u16 {
+     pub struct Style: u64 {
        const DEFAULT   = 0x8000;
 
        const BLACK     = 0x0001;
@@ -137,9 +161,140 @@ mod types {
        const BOLD      = 0x0200;
        const UNDERLINE = 0x0400;
        const REVERSE   = 0x0800;
+
+       // Made with `indexed` and `rgb`, which keep the color in COLOR_VALUE.
+       const INDEXED   = 0x1_0000;
+       const RGB       = 0x2_0000;
+       const COLOR_VALUE = 0xff_ffff_0000_0000;
      }
   }
 
+  // One of the 256 indexed colors: 0-7 are the named colors, 8-15 their
+  // bright versions, 16-231 a 6x6x6 color cube and 232-255 a grayscale ramp.
+  pub fn indexed(n: u8) -> Style {
+    Style::from_bits_truncate(INDEXED.bits | (n as u64) << 32)
+  }
+
+  pub fn rgb(r: u8, g: u8, b: u8) -> Style {
+    let value = (r as u64) << 16 | (g as u64) << 8 | b as u64;
+    Style::from_bits_truncate(RGB.bits | value << 32)
+  }
+
+  #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+  pub enum Color {
+    Default,
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+  }
+
+  // The levels of each component in the color cube.
+  const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+  // xterm's values for the named colors and their bright versions.
+  const SYSTEM: [(u8, u8, u8); 16] = [(0, 0, 0), (205, 0, 0), (0, 205, 0),
+                                      (205, 205, 0), (0, 0, 238),
+                                      (205, 0, 205), (0, 205, 205),
+                                      (229, 229, 229), (127, 127, 127),
+                                      (255, 0, 0), (0, 255, 0), (255, 255, 0),
+                                      (92, 92, 255), (255, 0, 255),
+                                      (0, 255, 255), (255, 255, 255)];
+
+  fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let d = |x: u8, y: u8| (x as i32 - y as i32) * (x as i32 - y as i32);
+    d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+  }
+
+  fn nearest_level(x: u8) -> usize {
+    (0..CUBE.len()).min_by_key(|&i| (CUBE[i] as i32 - x as i32).abs()).unwrap()
+  }
+
+  impl Color {
+    pub fn to_rgb(&self) -> Option<(u8, u8, u8)> {
+      match *self {
+        Color::Default => None,
+        Color::Indexed(n) if n < 16 => Some(SYSTEM[n as usize]),
+        Color::Indexed(n) if n < 232 => {
+          let n = n as usize - 16;
+          Some((CUBE[n / 36], CUBE[n / 6 % 6], CUBE[n % 6]))
+        }
+        Color::Indexed(n) => {
+          let level = 8 + 10 * (n - 232);
+          Some((level, level, level))
+        }
+        Color::Rgb(r, g, b) => Some((r, g, b)),
+      }
+    }
+
+    // The nearest color that can be shown in `mode`.
+    pub fn downgrade(&self, mode: OutputMode) -> Color {
+      let rgb = match self.to_rgb() {
+        Some(rgb) => rgb,
+        None => return Color::Default,
+      };
+      let cube = || {
+        let (r, g, b) = (nearest_level(rgb.0),
+                         nearest_level(rgb.1),
+                         nearest_level(rgb.2));
+        16 + 36 * r + 6 * g + b
+      };
+      let gray = || {
+        let level = (rgb.0 as usize + rgb.1 as usize + rgb.2 as usize) / 3;
+        232 + (level.saturating_sub(3) / 10).min(23)
+      };
+      let nearest = |candidates: &[usize]| {
+        let to_rgb = |n: usize| Color::Indexed(n as u8).to_rgb().unwrap();
+        *candidates.iter().min_by_key(|&&n| distance(rgb, to_rgb(n))).unwrap()
+      };
+      match (mode, *self) {
+        (OutputMode::Current, _) |
+        (OutputMode::TrueColor, _) => *self,
+        (OutputMode::Colors256, Color::Indexed(_)) => *self,
+        (OutputMode::Colors256, _) => {
+          Color::Indexed(nearest(&[cube(), gray()]) as u8)
+        }
+        (OutputMode::Colors216, _) => Color::Indexed(cube() as u8),
+        (OutputMode::Grayscale, _) => Color::Indexed(gray() as u8),
+        (OutputMode::Normal, Color::Indexed(n)) if n < 16 => {
+          Color::Indexed(n % 8)
+        }
+        (OutputMode::Normal, _) => {
+          Color::Indexed(nearest(&[0, 1, 2, 3, 4, 5, 6, 7]) as u8)
+        }
+      }
+    }
+  }
+
+  impl Style {
+    // The color of a foreground or background style. An `rgb` or `indexed`
+    // color wins over a named one.
+    pub fn color(&self) -> Color {
+      let value = ((self.bits & COLOR_VALUE.bits) >> 32) as u32;
+      if self.contains(RGB) {
+        Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+      } else if self.contains(INDEXED) {
+        Color::Indexed(value as u8)
+      } else if self.contains(WHITE) {
+        Color::Indexed(7)
+      } else if self.contains(MAGENTA) {
+        Color::Indexed(5)
+      } else if self.contains(CYAN) {
+        Color::Indexed(6)
+      } else if self.contains(YELLOW) {
+        Color::Indexed(3)
+      } else if self.contains(BLUE) {
+        Color::Indexed(4)
+      } else if self.contains(GREEN) {
+        Color::Indexed(2)
+      } else if self.contains(RED) {
+        Color::Indexed(1)
+      } else if self.contains(BLACK) {
+        Color::Indexed(0)
+      } else {
+        Color::Default
+      }
+    }
+  }
+
   #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
   pub struct Cell {
     pub ch: char,
@@ -147,6 +302,15 @@ mod types {
     pub bg: Style,
   }
 
+  // The char of the cell covered by the second half of a wide char.
+  pub const WIDE_CONTINUATION: char = '\0';
+
+  // The number of cells `ch` takes on screen. Wide chars, such as CJK and
+  // most emoji, take two and combining marks take none.
+  pub fn char_width(ch: char) -> usize { ch.width().unwrap_or(1) }
+
+  pub fn str_width(s: &str) -> usize { s.chars().map(char_width).sum() }
+
   pub trait Textbox {
     fn init() -> Result<Self> where Self: Sized;
 
@@ -172,10 +336,27 @@ mod types {
       self.put_cell(coord, Cell { ch: ch, fg: fg, bg: bg })
     }
 
-    fn set_cells(&mut self, coord: Coord, chs: &str, fg: Style, bg: Style) {
-      for (i, ch) in chs.chars().enumerate() {
-        self.set_cell(coord + i.to_col(), ch, fg, bg);
+    // Puts `ch` at `coord` followed by continuation cells if it is wide.
+    // Zero width chars are dropped. Returns the number of cells used.
+    fn set_char(&mut self, coord: Coord, ch: char, fg: Style, bg: Style)
+                -> usize {
+      let width = char_width(ch);
+      if width > 0 {
+        self.set_cell(coord, ch, fg, bg);
+      }
+      for col in 1..width {
+        self.set_cell(coord + col.to_col(), WIDE_CONTINUATION, fg, bg);
+      }
+      width
+    }
+
+    fn set_cells(&mut self, coord: Coord, chs: &str, fg: Style, bg: Style)
+                 -> usize {
+      let mut col = 0;
+      for ch in chs.chars() {
+        col += self.set_char(coord + col.to_col(), ch, fg, bg);
       }
+      col
     }
 
     fn set_input_mode(&mut self, _: InputMode) -> InputMode;
@@ -185,11 +366,24 @@ mod types {
   }
 }
 
+mod memory;
+pub use memory::MemoryTextbox;
+
 #[cfg(unix)]
+mod xterm;
+
+#[cfg(all(unix, feature = "ansi"))]
+mod ansi;
+#[cfg(all(unix, feature = "ansi"))]
+pub use ansi::*;
+#[cfg(all(unix, feature = "ansi"))]
+pub type TextboxImpl = ansi::AnsiTerminal;
+
+#[cfg(all(unix, feature = "termbox", not(feature = "ansi")))]
 mod nix;
-#[cfg(unix)]
+#[cfg(all(unix, feature = "termbox", not(feature = "ansi")))]
 pub use nix::*;
-#[cfg(unix)]
+#[cfg(all(unix, feature = "termbox", not(feature = "ansi")))]
 pub type TextboxImpl = nix::TermboxWrapper;
 
 #[cfg(windows)]
@@ -0,0 +1,169 @@
+// A Textbox over an in-memory grid of cells, fed from a scripted queue of
+// events, for testing code that draws to and reads from a Textbox.
+
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use types::*;
+
+fn blank() -> Cell { Cell { ch: ' ', fg: DEFAULT, bg: DEFAULT } }
+
+pub struct MemoryTextbox {
+  size: Coord,
+  back: Vec<Cell>,
+  screen: Vec<Cell>,
+  cursor: Option<Coord>,
+  screen_cursor: Option<Coord>,
+  fg_clear: Style,
+  bg_clear: Style,
+  input_mode: InputMode,
+  output_mode: OutputMode,
+  events: VecDeque<Event>,
+}
+
+impl MemoryTextbox {
+  pub fn new(size: Coord) -> Self {
+    MemoryTextbox {
+      size: size,
+      back: vec![blank(); size.0 * size.1],
+      screen: vec![blank(); size.0 * size.1],
+      cursor: None,
+      screen_cursor: None,
+      fg_clear: DEFAULT,
+      bg_clear: DEFAULT,
+      input_mode: InputMode::Esc,
+      output_mode: OutputMode::Normal,
+      events: VecDeque::new(),
+    }
+  }
+
+  pub fn push_event(&mut self, event: Event) { self.events.push_back(event); }
+
+  // Queues the keys typed to enter `keys`, decoded the way the terminal
+  // backends decode them: letters have their upper case as the key.
+  pub fn push_keys(&mut self, keys: &str) {
+    for ch in keys.chars() {
+      let event = match ch {
+        '\n' => Event::Key('\0', NO_MODS, Key::Enter),
+        '\t' => Event::Key('\0', NO_MODS, Key::Tab),
+        ch => Event::Key(ch, NO_MODS, Key::Char(ch.to_ascii_uppercase())),
+      };
+      self.push_event(event);
+    }
+  }
+
+  // Resizes the screen, clearing it, and queues the resize event.
+  pub fn resize(&mut self, size: Coord) {
+    self.size = size;
+    self.back = vec![blank(); size.0 * size.1];
+    self.screen = vec![blank(); size.0 * size.1];
+    self.cursor = None;
+    self.screen_cursor = None;
+    self.clear();
+    self.push_event(Event::Resize(size));
+  }
+
+  // The number of scripted events not yet popped.
+  pub fn pending(&self) -> usize { self.events.len() }
+
+  // The presented cell at `at`.
+  pub fn cell(&self, at: Coord) -> Cell {
+    self.screen[at.0 + at.1 * self.size.0]
+  }
+
+  // The presented cursor, if it is shown.
+  pub fn cursor(&self) -> Option<Coord> { self.screen_cursor }
+
+  // The presented text of `row` with trailing spaces trimmed.
+  pub fn row_text(&self, row: usize) -> String {
+    let cells = &self.screen[row * self.size.0..(row + 1) * self.size.0];
+    let text: String = cells.iter()
+      .map(|cell| cell.ch)
+      .filter(|&ch| ch != WIDE_CONTINUATION)
+      .collect();
+    text.trim_right().to_string()
+  }
+
+  // The presented screen as text, one line per row, followed by a line for
+  // each run of cells on a row not in the clear style, as
+  // "row:start-end fg on bg" with `end` exclusive.
+  pub fn snapshot(&self) -> String {
+    let mut out = String::new();
+    for row in 0..self.size.1 {
+      out.push_str(&self.row_text(row));
+      out.push('\n');
+    }
+    for row in 0..self.size.1 {
+      let mut col = 0;
+      while col < self.size.0 {
+        let cell = self.cell(Coord(col, row));
+        let start = col;
+        while col < self.size.0 {
+          let next = self.cell(Coord(col, row));
+          if (next.fg, next.bg) != (cell.fg, cell.bg) {
+            break;
+          }
+          col += 1;
+        }
+        if (cell.fg, cell.bg) != (self.fg_clear, self.bg_clear) {
+          writeln!(out, "{}:{}-{} {:?} on {:?}", row, start, col, cell.fg,
+                   cell.bg)
+            .unwrap();
+        }
+      }
+    }
+    out
+  }
+}
+
+impl Textbox for MemoryTextbox {
+  fn init() -> Result<Self> { Ok(MemoryTextbox::new(Coord(80, 24))) }
+
+  fn size(&self) -> Coord { self.size }
+
+  fn set_clear_style(&mut self, fg: Style, bg: Style) {
+    self.fg_clear = fg;
+    self.bg_clear = bg;
+  }
+  fn clear(&mut self) {
+    for cell in self.back.iter_mut() {
+      cell.ch = ' ';
+      cell.fg = self.fg_clear;
+      cell.bg = self.bg_clear;
+    }
+  }
+
+  fn present(&mut self) {
+    self.screen.copy_from_slice(&self.back);
+    self.screen_cursor = self.cursor;
+  }
+
+  fn set_cursor(&mut self, coord: Coord) {
+    if coord.0 < self.size.0 && coord.1 < self.size.1 {
+      self.cursor = Some(coord);
+    }
+  }
+  fn hide_cursor(&mut self) { self.cursor = None; }
+
+  fn put_cell(&mut self, coord: Coord, cell: Cell) {
+    if coord.0 < self.size.0 && coord.1 < self.size.1 {
+      self.back[coord.0 + coord.1 * self.size.0] = cell
+    }
+  }
+
+  fn set_input_mode(&mut self, mode: InputMode) -> InputMode {
+    if mode != InputMode::Current {
+      self.input_mode = mode;
+    }
+    self.input_mode
+  }
+  fn set_output_mode(&mut self, mode: OutputMode) -> OutputMode {
+    if mode != OutputMode::Current {
+      self.output_mode = mode;
+    }
+    self.output_mode
+  }
+
+  // Returns None once the scripted events run out.
+  fn pop_event(&mut self) -> Option<Event> { self.events.pop_front() }
+}
@@ -6,16 +6,25 @@ extern crate termbox_sys;
 
 use self::termbox_sys::*;
 use std::char;
+use std::collections::VecDeque;
 use std::ops::Drop;
 use std::os::raw::c_int;
 
 pub use types::*;
+use xterm::to_seq_event;
 
 #[derive(Debug)]
 pub struct TermboxWrapper {
+  // Events read ahead while decoding an escape sequence.
+  pending: VecDeque<Event>,
+  output_mode: OutputMode,
+  fg_clear: Style,
+  bg_clear: Style,
 }
 
-fn to_tb_style(s: Style) -> u16 {
+// Converts a style for termbox's `mode`. Termbox only has the terminal's
+// default colors in normal mode so `default` is used for them otherwise.
+fn to_tb_style(s: Style, mode: OutputMode, default: u8) -> u16 {
   let mut tb = TB_DEFAULT;
 
   if s.contains(BRIGHT) {
@@ -31,30 +40,25 @@ fn to_tb_style(s: Style) -> u16 {
     tb |= TB_UNDERLINE
   }
 
-  if s.contains(WHITE) {
-    tb |= TB_WHITE
-  } else if s.contains(MAGENTA) {
-    tb |= TB_MAGENTA
-  } else if s.contains(CYAN) {
-    tb |= TB_CYAN
-  } else if s.contains(YELLOW) {
-    tb |= TB_YELLOW
-  } else if s.contains(BLUE) {
-    tb |= TB_BLUE
-  } else if s.contains(GREEN) {
-    tb |= TB_GREEN
-  } else if s.contains(RED) {
-    tb |= TB_RED
-  } else if s.contains(BLACK) {
-    tb |= TB_BLACK
-  } else if s.contains(DEFAULT) {
-    tb |= TB_DEFAULT
+  let color = match s.color() {
+    Color::Default if mode != OutputMode::Normal => Color::Indexed(default),
+    color => color,
+  };
+  if let Color::Indexed(n) = color.downgrade(mode) {
+    tb |= match mode {
+      OutputMode::Colors256 => n as u16,
+      OutputMode::Colors216 => n as u16 - 16,
+      OutputMode::Grayscale => n as u16 - 232,
+      _ => TB_BLACK + n as u16,
+    }
   }
 
   tb
 }
 
-fn to_mods(raw: u8) -> Mod { NO_MODS }
+fn to_mods(raw: u8) -> Mod {
+  if raw & TB_MOD_ALT != 0 { ALT } else { NO_MODS }
+}
 
 fn to_event(raw: RawEvent) -> Option<Event> {
   if raw.etype == TB_EVENT_KEY {
@@ -70,7 +74,8 @@ fn to_event(raw: RawEvent) -> Option<Event> {
       kc if kc == TB_KEY_END => Key::End,
       kc if kc == TB_KEY_PGUP => Key::PageUp,
       kc if kc == TB_KEY_PGDN => Key::PageDown,
-      kc if /*kc == TB_KEY_BACKSPACE || */kc == TB_KEY_BACKSPACE2 => Key::Backspace,
+      kc if kc == TB_KEY_BACKSPACE2 => Key::Backspace,
+      kc if kc == TB_KEY_INSERT => Key::Insert,
       kc if kc == TB_KEY_DELETE => Key::Delete,
       kc if kc == TB_KEY_TAB => Key::Tab,
       kc if kc == TB_KEY_ENTER => Key::Enter,
@@ -78,54 +83,110 @@ fn to_event(raw: RawEvent) -> Option<Event> {
         ch = ' ';
         Key::Char(' ')
       }
-      kc if kc == TB_KEY_CTRL_F => {
-        mods |= CTRL;
-        Key::Char('F')
-      }
-      kc if kc == TB_KEY_CTRL_G => {
-        mods |= CTRL;
-        Key::Char('G')
+      // Termbox's key codes count down from 0xFFFF, which is F1, so the F
+      // keys are the twelve highest.
+      kc if kc >= TB_KEY_F12 => {
+        Key::F((TB_KEY_F1 - kc + 1) as u8)
       }
-      kc if kc == TB_KEY_CTRL_H => {
+      // Ctrl+H is also the backspace key on some terminals; it is left as
+      // Ctrl+H since view binds it to cursor left.
+      kc if kc >= TB_KEY_CTRL_A && kc <= TB_KEY_CTRL_Z => {
         mods |= CTRL;
-        Key::Char('H')
+        Key::Char((b'A' + (kc - TB_KEY_CTRL_A) as u8) as char)
       }
-      kc if kc == TB_KEY_CTRL_J => {
+      kc if kc == TB_KEY_CTRL_BACKSLASH => {
         mods |= CTRL;
-        Key::Char('J')
+        Key::Char('\\')
       }
-      kc if kc == TB_KEY_CTRL_K => {
+      kc if kc == TB_KEY_CTRL_RSQ_BRACKET => {
         mods |= CTRL;
-        Key::Char('K')
+        Key::Char(']')
       }
-      kc if kc == TB_KEY_CTRL_L => {
+      kc if kc == TB_KEY_CTRL_6 => {
         mods |= CTRL;
-        Key::Char('L')
+        Key::Char('6')
       }
-      kc if kc == TB_KEY_CTRL_S => {
+      kc if kc == TB_KEY_CTRL_SLASH => {
         mods |= CTRL;
-        Key::Char('S')
+        Key::Char('/')
       }
-      kc if kc == TB_KEY_CTRL_Q => {
+      // Ctrl+Space and Ctrl+2 both send a NUL.
+      0 if ch == '\0' => {
         mods |= CTRL;
-        Key::Char('Q')
+        Key::Char(' ')
       }
-      // kc if kc >= w::VK_F1 as u16 && kc <= w::VK_F24 as u16 => {
-      //   Key::F((kc - w::VK_F1 as u16 + 1) as u8)
-      // }
-      // // Doesn't handle shift.
-      // // Letters
-      // kc if kc >= 65 && kc <= 90 => Key::Char(kc as u8 as char),
-      // // Numbers
-      // kc if kc >= 48 && kc <= 57 => Key::Char(kc as u8 as char),
+      // Printable characters. Like Windows' virtual key codes, letters are
+      // reported in upper case.
+      0 => Key::Char(ch.to_ascii_uppercase()),
       _ => Key::Char('\0'),
     };
     Some(Event::Key(ch, mods, kc))
+  } else if raw.etype == TB_EVENT_MOUSE {
+    let button = match raw.key {
+      kc if kc == TB_KEY_MOUSE_LEFT => Button::Left,
+      kc if kc == TB_KEY_MOUSE_MIDDLE => Button::Middle,
+      kc if kc == TB_KEY_MOUSE_RIGHT => Button::Right,
+      kc if kc == TB_KEY_MOUSE_RELEASE => Button::Release,
+      kc if kc == TB_KEY_MOUSE_WHEEL_UP => Button::WheelUp,
+      kc if kc == TB_KEY_MOUSE_WHEEL_DOWN => Button::WheelDown,
+      _ => return None,
+    };
+    let at = Coord(raw.x as usize, raw.y as usize);
+    Some(Event::Mouse(button, to_mods(raw.emod), at))
+  } else if raw.etype == TB_EVENT_RESIZE {
+    Some(Event::Resize(Coord(raw.w as usize, raw.h as usize)))
   } else {
     None
   }
 }
 
+// Reads the next event, returning None if `wait` is false and there is no
+// input already waiting.
+fn read_event(wait: bool) -> Option<Event> {
+  use std::mem::zeroed;
+  unsafe {
+    let mut raw: RawEvent = zeroed();
+    let ty = if wait {
+      tb_poll_event(&mut raw as *mut RawEvent)
+    } else {
+      tb_peek_event(&mut raw as *mut RawEvent, 0)
+    };
+    if ty > 0 { to_event(raw) } else { None }
+  }
+}
+
+impl TermboxWrapper {
+  // Decodes the keys that start with an Esc which termbox reports as an Esc
+  // followed by more keys: Alt+key and xterm style modified keys. Only input
+  // that has already arrived is looked at so a lone Esc is not delayed.
+  fn escape(&mut self) -> Option<Event> {
+    while let Some(event) = read_event(false) {
+      self.pending.push_back(event);
+    }
+    let chars: Vec<char> = self.pending
+      .iter()
+      .map(|event| match *event {
+        Event::Key(ch, NO_MODS, Key::Char(_)) => ch,
+        _ => '\0',
+      })
+      .take_while(|&ch| ch != '\0')
+      .collect();
+
+    if let Some((event, len)) = to_seq_event(&chars) {
+      self.pending.drain(..len);
+      return Some(event);
+    }
+
+    match self.pending.front().cloned() {
+      Some(Event::Key(ch, mods, kc)) if kc != Key::Escape => {
+        self.pending.pop_front();
+        Some(Event::Key(ch, mods | ALT, kc))
+      }
+      _ => Some(Event::Key('\0', NO_MODS, Key::Escape)),
+    }
+  }
+}
+
 impl Textbox for TermboxWrapper {
   fn init() -> Result<Self> {
     unsafe {
@@ -134,7 +195,12 @@ impl Textbox for TermboxWrapper {
         panic!("tb_init failed! {}", err);
       }
     }
-    Ok(TermboxWrapper {})
+    Ok(TermboxWrapper {
+      pending: VecDeque::new(),
+      output_mode: OutputMode::Normal,
+      fg_clear: DEFAULT,
+      bg_clear: DEFAULT,
+    })
   }
 
   fn size(&self) -> Coord {
@@ -144,8 +210,10 @@ impl Textbox for TermboxWrapper {
   }
 
   fn set_clear_style(&mut self, fg: Style, bg: Style) {
-    let tb_fg = to_tb_style(fg);
-    let tb_bg = to_tb_style(bg);
+    self.fg_clear = fg;
+    self.bg_clear = bg;
+    let tb_fg = to_tb_style(fg, self.output_mode, 7);
+    let tb_bg = to_tb_style(bg, self.output_mode, 0);
     unsafe { tb_set_clear_attributes(tb_fg, tb_bg) }
   }
   fn clear(&mut self) { unsafe { tb_clear() } }
@@ -161,24 +229,60 @@ impl Textbox for TermboxWrapper {
 
   fn put_cell(&mut self, coord: Coord, cell: Cell) {
     let tb_ch = cell.ch as u32;
-    let tb_fg = to_tb_style(cell.fg);
-    let tb_bg = to_tb_style(cell.bg);
+    let tb_fg = to_tb_style(cell.fg, self.output_mode, 7);
+    let tb_bg = to_tb_style(cell.bg, self.output_mode, 0);
     unsafe {
       tb_change_cell(coord.0 as c_int, coord.1 as c_int, tb_ch, tb_fg, tb_bg)
     }
   }
 
-  fn set_input_mode(&mut self, _: InputMode) -> InputMode { unimplemented!() }
-  fn set_output_mode(&mut self, _: OutputMode) -> OutputMode {
-    unimplemented!()
+  fn set_input_mode(&mut self, mode: InputMode) -> InputMode {
+    let tb_mode = match mode {
+      InputMode::Current => TB_INPUT_CURRENT,
+      InputMode::Esc => TB_INPUT_ESC,
+      InputMode::Alt => TB_INPUT_ALT,
+      // Termbox needs Esc or Alt handling alongside mouse input.
+      InputMode::Mouse => TB_INPUT_ESC | TB_INPUT_MOUSE,
+    };
+    let tb_mode = unsafe { tb_select_input_mode(tb_mode) };
+    if tb_mode & TB_INPUT_MOUSE != 0 {
+      InputMode::Mouse
+    } else if tb_mode & TB_INPUT_ALT != 0 {
+      InputMode::Alt
+    } else {
+      InputMode::Esc
+    }
+  }
+  fn set_output_mode(&mut self, mode: OutputMode) -> OutputMode {
+    let tb_mode = match mode {
+      OutputMode::Current => TB_OUTPUT_CURRENT,
+      OutputMode::Normal => TB_OUTPUT_NORMAL,
+      // Termbox has no 24-bit output so RGB is matched to the 256 colors.
+      OutputMode::Colors256 | OutputMode::TrueColor => TB_OUTPUT_256,
+      OutputMode::Colors216 => TB_OUTPUT_216,
+      OutputMode::Grayscale => TB_OUTPUT_GRAYSCALE,
+    };
+    let tb_mode = unsafe { tb_select_output_mode(tb_mode) };
+    self.output_mode = match tb_mode {
+      m if m == TB_OUTPUT_256 => OutputMode::Colors256,
+      m if m == TB_OUTPUT_216 => OutputMode::Colors216,
+      m if m == TB_OUTPUT_GRAYSCALE => OutputMode::Grayscale,
+      _ => OutputMode::Normal,
+    };
+    // The clear style is kept by termbox already converted.
+    let (fg, bg) = (self.fg_clear, self.bg_clear);
+    self.set_clear_style(fg, bg);
+    self.output_mode
   }
 
   fn pop_event(&mut self) -> Option<Event> {
-    use std::mem::uninitialized;
-    unsafe {
-      let mut raw: RawEvent = uninitialized();
-      let ty = tb_poll_event(&mut raw as *mut RawEvent);
-      if ty > 0 { to_event(raw) } else { None }
+    let event = match self.pending.pop_front() {
+      Some(event) => Some(event),
+      None => read_event(true),
+    };
+    match event {
+      Some(Event::Key(_, NO_MODS, Key::Escape)) => self.escape(),
+      event => event,
     }
   }
 }
@@ -190,3 +294,42 @@ impl Drop for TermboxWrapper {
     }
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key_event(key: u16, ch: char, emod: u8) -> Option<Event> {
+    to_event(RawEvent {
+      etype: TB_EVENT_KEY,
+      emod: emod,
+      key: key,
+      ch: ch as u32,
+      w: 0,
+      h: 0,
+      x: 0,
+      y: 0,
+    })
+  }
+
+  #[test]
+  fn decodes_termbox_keys() {
+    let cases = [(TB_KEY_F1, NO_MODS, Key::F(1)),
+                 (TB_KEY_F3, NO_MODS, Key::F(3)),
+                 (TB_KEY_F12, NO_MODS, Key::F(12)),
+                 (TB_KEY_INSERT, NO_MODS, Key::Insert),
+                 (TB_KEY_ARROW_UP, ALT, Key::Up),
+                 (TB_KEY_ENTER, NO_MODS, Key::Enter),
+                 (TB_KEY_CTRL_Q, CTRL, Key::Char('Q')),
+                 (TB_KEY_CTRL_SLASH, CTRL, Key::Char('/'))];
+    for &(key, mods, kc) in cases.iter() {
+      let emod = if mods.contains(ALT) { TB_MOD_ALT } else { 0 };
+      assert_eq!(key_event(key, '\0', emod),
+                 Some(Event::Key('\0', mods, kc)),
+                 "key {:x}",
+                 key);
+    }
+    assert_eq!(key_event(0, 'q', 0),
+               Some(Event::Key('q', NO_MODS, Key::Char('Q'))));
+  }
+}
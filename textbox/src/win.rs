@@ -41,22 +41,25 @@ fn to_fg(s: Style) -> u16 {
     fg |= w::COMMON_LVB_UNDERSCORE
   }
 
-  if s.contains(WHITE) || s.contains(DEFAULT) {
-    fg |= w::FOREGROUND_RED | w::FOREGROUND_GREEN | w::FOREGROUND_BLUE;
-  } else if s.contains(MAGENTA) {
-    fg |= w::FOREGROUND_RED | w::FOREGROUND_BLUE;
-  } else if s.contains(CYAN) {
-    fg |= w::FOREGROUND_GREEN | w::FOREGROUND_BLUE;
-  } else if s.contains(YELLOW) {
-    fg |= w::FOREGROUND_RED | w::FOREGROUND_GREEN;
-  } else if s.contains(BLUE) {
-    fg |= w::FOREGROUND_BLUE;
-  } else if s.contains(GREEN) {
-    fg |= w::FOREGROUND_GREEN;
-  } else if s.contains(RED) {
-    fg |= w::FOREGROUND_RED;
-  } else if s.contains(BLACK) {
-    // do nothing
+  let color = s.color();
+  if let Color::Indexed(n) = color {
+    if n >= 8 && n < 16 {
+      fg |= w::FOREGROUND_INTENSITY;
+    }
+  }
+  match color.downgrade(OutputMode::Normal) {
+    Color::Indexed(n) => {
+      if n & 1 != 0 {
+        fg |= w::FOREGROUND_RED;
+      }
+      if n & 2 != 0 {
+        fg |= w::FOREGROUND_GREEN;
+      }
+      if n & 4 != 0 {
+        fg |= w::FOREGROUND_BLUE;
+      }
+    }
+    _ => fg |= w::FOREGROUND_RED | w::FOREGROUND_GREEN | w::FOREGROUND_BLUE,
   }
 
   fg
@@ -80,20 +83,22 @@ fn to_bg(s: Style) -> u16 {
     bg |= w::COMMON_LVB_UNDERSCORE
   }
 
-  if s.contains(WHITE) {
-    bg |= w::BACKGROUND_RED | w::BACKGROUND_GREEN | w::BACKGROUND_BLUE;
-  } else if s.contains(MAGENTA) {
-    bg |= w::BACKGROUND_RED | w::BACKGROUND_BLUE;
-  } else if s.contains(CYAN) {
-    bg |= w::BACKGROUND_GREEN | w::BACKGROUND_BLUE;
-  } else if s.contains(YELLOW) {
-    bg |= w::BACKGROUND_RED | w::BACKGROUND_GREEN;
-  } else if s.contains(BLUE) {
-    bg |= w::BACKGROUND_BLUE;
-  } else if s.contains(GREEN) {
-    bg |= w::BACKGROUND_GREEN;
-  } else if s.contains(RED) {
-    bg |= w::BACKGROUND_RED;
+  let color = s.color();
+  if let Color::Indexed(n) = color {
+    if n >= 8 && n < 16 {
+      bg |= w::BACKGROUND_INTENSITY;
+    }
+  }
+  if let Color::Indexed(n) = color.downgrade(OutputMode::Normal) {
+    if n & 1 != 0 {
+      bg |= w::BACKGROUND_RED;
+    }
+    if n & 2 != 0 {
+      bg |= w::BACKGROUND_GREEN;
+    }
+    if n & 4 != 0 {
+      bg |= w::BACKGROUND_BLUE;
+    }
   }
 
   bg
@@ -185,10 +190,71 @@ fn to_event(raw: Input) -> Option<Event> {
         None
       }
     }
+    Input::Mouse { position, button_state, control_key_state, event_flags } => {
+      let mods = to_mod(control_key_state);
+      let at = Coord(position.0 as usize, position.1 as usize);
+      let button = if event_flags & w::MOUSE_WHEELED > 0 {
+        // The high word of the button state is the signed wheel delta.
+        if (button_state as i32) < 0 {
+          Button::WheelDown
+        } else {
+          Button::WheelUp
+        }
+      } else if event_flags & w::MOUSE_MOVED > 0 {
+        return None;
+      } else if button_state & w::FROM_LEFT_1ST_BUTTON_PRESSED > 0 {
+        Button::Left
+      } else if button_state & w::RIGHTMOST_BUTTON_PRESSED > 0 {
+        Button::Right
+      } else if button_state & w::FROM_LEFT_2ND_BUTTON_PRESSED > 0 {
+        Button::Middle
+      } else {
+        Button::Release
+      };
+      Some(Event::Mouse(button, mods, at))
+    }
+    Input::WindowBufferSize(cols, rows) => {
+      Some(Event::Resize(Coord(cols as usize, rows as usize)))
+    }
     _ => None,
   }
 }
 
+impl WinConsoleWrapper {
+  // Reallocates the back buffer for the new console size. Everything is
+  // redrawn on the next present.
+  fn resize(&mut self, size: Coord) {
+    self.backbuf = vec![Cell { ch: ' ', fg: self.fg_clear, bg: self.bg_clear, };
+                        size.0 * size.1]
+      .into_boxed_slice();
+    self.size = size;
+    self.clear();
+  }
+
+  fn next_event(&mut self) -> Option<Event> {
+    if self.events.len() > 0 {
+      to_event(self.events.pop_front().unwrap())
+    } else
+    // if self.stdin.available_input().unwrap_or(0) > 0
+    {
+      match self.stdin.read_input() {
+        Ok(inputs) => {
+          self.events.extend(inputs);
+          if let Some(e) = to_event(self.events.pop_front().unwrap()) {
+            Some(e)
+          } else {
+            // TODO: Possible stack overflow.
+            self.next_event()
+          }
+        }
+        Err(_) => None,
+      }
+      // } else {
+      //   None
+    }
+  }
+}
+
 impl Textbox for WinConsoleWrapper {
   fn init() -> Result<Self> {
     let stdin = InputBuffer::from_conin().unwrap();
@@ -271,32 +337,18 @@ impl Textbox for WinConsoleWrapper {
     }
   }
 
-  fn set_input_mode(&mut self, _: InputMode) -> InputMode { unimplemented!() }
+  // The console is always opened with mouse input enabled.
+  fn set_input_mode(&mut self, _: InputMode) -> InputMode { InputMode::Mouse }
   fn set_output_mode(&mut self, _: OutputMode) -> OutputMode {
     OutputMode::Normal
   }
 
   fn pop_event(&mut self) -> Option<Event> {
-    if self.events.len() > 0 {
-      to_event(self.events.pop_front().unwrap())
-    } else
-    // if self.stdin.available_input().unwrap_or(0) > 0
-    {
-      match self.stdin.read_input() {
-        Ok(inputs) => {
-          self.events.extend(inputs);
-          if let Some(e) = to_event(self.events.pop_front().unwrap()) {
-            Some(e)
-          } else {
-            // TODO: Possible stack overflow.
-            self.pop_event()
-          }
-        }
-        Err(_) => None,
-      }
-      // } else {
-      //   None
+    let event = self.next_event();
+    if let Some(Event::Resize(size)) = event {
+      self.resize(size);
     }
+    event
   }
 }
 
@@ -0,0 +1,141 @@
+// Decoding of the xterm style escape sequences terminals send for keys.
+
+use std::char;
+use types::*;
+
+// Decodes the modifier parameter of an xterm style key sequence, which is one
+// more than a bit set of shift (1), alt (2) and ctrl (4).
+pub fn to_xterm_mods(param: u32) -> Mod {
+  let bits = param.saturating_sub(1);
+  let mut mods = NO_MODS;
+  if bits & 1 != 0 {
+    mods |= SHIFT
+  }
+  if bits & 2 != 0 {
+    mods |= ALT
+  }
+  if bits & 4 != 0 {
+    mods |= CTRL
+  }
+  mods
+}
+
+// Decodes the parameters and final char of a "\x1b[" key sequence, such as
+// Shift+Up as "\x1b[1;2A" or, with xterm's modifyOtherKeys, Ctrl+Shift+K as
+// "\x1b[27;6;75~". "\x1bO" sequences decode the same with no parameters.
+pub fn to_csi_event(params: &[u32], fin: char) -> Option<Event> {
+  let param = |i: usize| params.get(i).cloned().unwrap_or(1);
+  let mut ch = '\0';
+  let kc = match fin {
+    'A' => Key::Up,
+    'B' => Key::Down,
+    'C' => Key::Right,
+    'D' => Key::Left,
+    'H' => Key::Home,
+    'F' => Key::End,
+    'P' => Key::F(1),
+    'Q' => Key::F(2),
+    'R' => Key::F(3),
+    'S' => Key::F(4),
+    '~' => {
+      match param(0) {
+        1 | 7 => Key::Home,
+        2 => Key::Insert,
+        3 => Key::Delete,
+        4 | 8 => Key::End,
+        5 => Key::PageUp,
+        6 => Key::PageDown,
+        n if n >= 11 && n <= 15 => Key::F((n - 10) as u8),
+        n if n >= 17 && n <= 21 => Key::F((n - 11) as u8),
+        n if n >= 23 && n <= 24 => Key::F((n - 12) as u8),
+        27 => {
+          ch = match char::from_u32(param(2)) {
+            Some(ch) => ch,
+            None => return None,
+          };
+          Key::Char(ch.to_ascii_uppercase())
+        }
+        _ => return None,
+      }
+    }
+    _ => return None,
+  };
+  Some(Event::Key(ch, to_xterm_mods(param(1)), kc))
+}
+
+// Decodes the "\x1b[" or "\x1bO" sequence at the start of `chars`, the keys
+// read after an Esc. SS3 sequences, "\x1bO", are sent for F1-F4 and, in
+// application cursor mode, the arrows. Returns the event and the number of
+// chars it took, or None if the sequence is unknown or incomplete.
+pub fn to_seq_event(chars: &[char]) -> Option<(Event, usize)> {
+  if chars.first() != Some(&'[') && chars.first() != Some(&'O') {
+    return None;
+  }
+  let end = match chars.iter().skip(1).position(|&ch| ch >= '@' && ch <= '~') {
+    Some(end) => end + 1,
+    None => return None,
+  };
+  let params: String = chars[1..end].iter().cloned().collect();
+  let params: Vec<u32> = params.split(';')
+    .map(|param| param.parse().unwrap_or(1))
+    .collect();
+  to_csi_event(&params, chars[end]).map(|event| (event, end + 1))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn key(mods: Mod, kc: Key) -> Event { Event::Key('\0', mods, kc) }
+
+  #[test]
+  fn decodes_modifier_params() {
+    let cases = [(0, NO_MODS),
+                 (1, NO_MODS),
+                 (2, SHIFT),
+                 (3, ALT),
+                 (5, CTRL),
+                 (6, CTRL | SHIFT),
+                 (8, CTRL | ALT | SHIFT)];
+    for &(param, mods) in cases.iter() {
+      assert_eq!(to_xterm_mods(param), mods, "param {}", param);
+    }
+  }
+
+  #[test]
+  fn decodes_csi_and_ss3_sequences() {
+    let cases = [("[A", key(NO_MODS, Key::Up)),
+                 ("[1;5A", key(CTRL, Key::Up)),
+                 ("[1;2D", key(SHIFT, Key::Left)),
+                 ("[H", key(NO_MODS, Key::Home)),
+                 ("[4~", key(NO_MODS, Key::End)),
+                 ("[3;3~", key(ALT, Key::Delete)),
+                 ("[6~", key(NO_MODS, Key::PageDown)),
+                 ("OP", key(NO_MODS, Key::F(1))),
+                 ("OR", key(NO_MODS, Key::F(3))),
+                 ("OA", key(NO_MODS, Key::Up)),
+                 ("[15~", key(NO_MODS, Key::F(5))),
+                 ("[21~", key(NO_MODS, Key::F(10))),
+                 ("[24;5~", key(CTRL, Key::F(12))),
+                 ("[27;6;75~", Event::Key('K', CTRL | SHIFT, Key::Char('K'))),
+                 ("[27;3;107~", Event::Key('k', ALT, Key::Char('K')))];
+    for &(seq, event) in cases.iter() {
+      let chars: Vec<char> = seq.chars().collect();
+      assert_eq!(to_seq_event(&chars), Some((event, chars.len())), "{}", seq);
+    }
+  }
+
+  #[test]
+  fn takes_only_the_sequence() {
+    let chars: Vec<char> = "[Bx".chars().collect();
+    assert_eq!(to_seq_event(&chars), Some((key(NO_MODS, Key::Down), 2)));
+  }
+
+  #[test]
+  fn leaves_unknown_and_incomplete_sequences() {
+    for seq in ["", "x", "[", "[1;5", "O", "[99~", "[Z"].iter() {
+      let chars: Vec<char> = seq.chars().collect();
+      assert_eq!(to_seq_event(&chars), None, "{}", seq);
+    }
+  }
+}
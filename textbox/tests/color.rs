@@ -0,0 +1,61 @@
+extern crate textbox;
+
+use textbox::*;
+
+#[test]
+fn reads_the_color_of_a_style() {
+  assert_eq!(DEFAULT.color(), Color::Default);
+  assert_eq!((RED | BOLD).color(), Color::Indexed(1));
+  assert_eq!(WHITE.color(), Color::Indexed(7));
+  assert_eq!(indexed(200).color(), Color::Indexed(200));
+  assert_eq!(rgb(1, 2, 3).color(), Color::Rgb(1, 2, 3));
+  // An indexed or rgb color wins over a named one.
+  assert_eq!((rgb(1, 2, 3) | RED).color(), Color::Rgb(1, 2, 3));
+  assert_eq!((indexed(0) | UNDERLINE).color(), Color::Indexed(0));
+}
+
+#[test]
+fn converts_indexed_colors_to_rgb() {
+  let cases = [(Color::Default, None),
+               (Color::Indexed(1), Some((205, 0, 0))),
+               (Color::Indexed(9), Some((255, 0, 0))),
+               (Color::Indexed(16), Some((0, 0, 0))),
+               (Color::Indexed(196), Some((255, 0, 0))),
+               (Color::Indexed(110), Some((135, 175, 215))),
+               (Color::Indexed(231), Some((255, 255, 255))),
+               (Color::Indexed(232), Some((8, 8, 8))),
+               (Color::Indexed(255), Some((238, 238, 238))),
+               (Color::Rgb(1, 2, 3), Some((1, 2, 3)))];
+  for &(color, rgb) in cases.iter() {
+    assert_eq!(color.to_rgb(), rgb, "{:?}", color);
+  }
+}
+
+#[test]
+fn downgrades_to_the_nearest_color_of_the_mode() {
+  use OutputMode::*;
+  let cases = [(Color::Rgb(255, 0, 0), Colors256, Color::Indexed(196)),
+               (Color::Rgb(0, 0, 0), Colors256, Color::Indexed(16)),
+               (Color::Rgb(255, 255, 255), Colors256, Color::Indexed(231)),
+               // Grays between the cube's levels use the gray ramp.
+               (Color::Rgb(128, 128, 128), Colors256, Color::Indexed(244)),
+               (Color::Rgb(8, 8, 8), Colors256, Color::Indexed(232)),
+               (Color::Indexed(100), Colors256, Color::Indexed(100)),
+               (Color::Rgb(0, 255, 0), Colors216, Color::Indexed(46)),
+               (Color::Rgb(128, 128, 128), Colors216, Color::Indexed(102)),
+               (Color::Indexed(244), Colors216, Color::Indexed(102)),
+               (Color::Rgb(0, 0, 0), Grayscale, Color::Indexed(232)),
+               (Color::Rgb(255, 255, 255), Grayscale, Color::Indexed(255)),
+               (Color::Rgb(250, 10, 10), Normal, Color::Indexed(1)),
+               (Color::Rgb(0, 0, 230), Normal, Color::Indexed(4)),
+               (Color::Rgb(255, 255, 255), Normal, Color::Indexed(7)),
+               (Color::Indexed(9), Normal, Color::Indexed(1)),
+               (Color::Indexed(196), Normal, Color::Indexed(1)),
+               (Color::Indexed(232), Normal, Color::Indexed(0)),
+               (Color::Rgb(1, 2, 3), TrueColor, Color::Rgb(1, 2, 3)),
+               (Color::Default, Normal, Color::Default),
+               (Color::Default, Colors256, Color::Default)];
+  for &(color, mode, downgraded) in cases.iter() {
+    assert_eq!(color.downgrade(mode), downgraded, "{:?} {:?}", color, mode);
+  }
+}
@@ -0,0 +1,35 @@
+extern crate textbox;
+
+use textbox::*;
+
+#[test]
+fn presents_cells_and_cursor() {
+  let mut tbox = MemoryTextbox::new(Coord(10, 2));
+  tbox.set_cells(Coord(1, 0), "ab日c", DEFAULT, DEFAULT);
+  tbox.set_cells(Coord(0, 1), "xy", YELLOW | REVERSE, DEFAULT);
+  tbox.set_cursor(Coord(2, 1));
+  assert_eq!(tbox.row_text(0), "");
+  assert_eq!(tbox.cursor(), None);
+
+  tbox.present();
+  assert_eq!(tbox.snapshot(),
+             " ab日c\nxy\n1:0-2 YELLOW | REVERSE on DEFAULT\n");
+  assert_eq!(tbox.cell(Coord(4, 0)).ch, WIDE_CONTINUATION);
+  assert_eq!(tbox.cursor(), Some(Coord(2, 1)));
+}
+
+#[test]
+fn pops_scripted_events() {
+  let mut tbox = MemoryTextbox::new(Coord(10, 2));
+  tbox.push_keys("a\n");
+  tbox.push_event(Event::Key('\0', CTRL, Key::Char('Q')));
+  tbox.resize(Coord(20, 5));
+  assert_eq!(tbox.pending(), 4);
+
+  assert_eq!(tbox.pop_event(), Some(Event::Key('a', NO_MODS, Key::Char('A'))));
+  assert_eq!(tbox.pop_event(), Some(Event::Key('\0', NO_MODS, Key::Enter)));
+  assert_eq!(tbox.pop_event(), Some(Event::Key('\0', CTRL, Key::Char('Q'))));
+  assert_eq!(tbox.pop_event(), Some(Event::Resize(Coord(20, 5))));
+  assert_eq!(tbox.pop_event(), None);
+  assert_eq!(tbox.size(), Coord(20, 5));
+}